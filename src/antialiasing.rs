@@ -0,0 +1,243 @@
+use nalgebra_glm::Vec2;
+use std::f32::consts::PI;
+
+// Standard rotated-grid patterns, as offsets inside the pixel
+const MSAA_2X: [(f32, f32); 2] = [(0.75, 0.75), (0.25, 0.25)];
+const MSAA_4X: [(f32, f32); 4] = [(0.375, 0.125), (0.875, 0.375), (0.125, 0.625), (0.625, 0.875)];
+const MSAA_8X: [(f32, f32); 8] = [
+    (0.5625, 0.3125), (0.4375, 0.6875), (0.8125, 0.5625), (0.3125, 0.1875),
+    (0.1875, 0.8125), (0.0625, 0.4375), (0.6875, 0.9375), (0.9375, 0.0625),
+];
+const LANCZOS_LOBES: f32 = 2.0;
+
+/// Filter used to bring supersampled images down to the framebuffer size.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Downsample {
+    Box,
+    Lanczos,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Antialiasing {
+    Off,
+    // Coverage and depth per sample, but the shader runs once per pixel
+    Msaa(usize),
+    // A grid of factor x factor samples per pixel, each one shaded
+    Ssaa(usize, Downsample),
+}
+
+impl Antialiasing {
+    // "off", "msaa2", "msaa4", "msaa8", "ssaa2" to "ssaa4", optionally with "-lanczos"
+    pub fn parse(name: &str) -> Option<Antialiasing> {
+        let (name, downsample) = match name.strip_suffix("-lanczos") {
+            Some(name) => (name, Downsample::Lanczos),
+            None => (name.strip_suffix("-box").unwrap_or(name), Downsample::Box),
+        };
+        if name == "off" {
+            return Some(Antialiasing::Off);
+        }
+        if let Some(samples) = name.strip_prefix("msaa") {
+            return match samples.parse().ok()? {
+                samples @ (2 | 4 | 8) if downsample == Downsample::Box => Some(Antialiasing::Msaa(samples)),
+                _ => None,
+            };
+        }
+        match name.strip_prefix("ssaa")?.parse().ok()? {
+            factor @ 2..=4 => Some(Antialiasing::Ssaa(factor, downsample)),
+            _ => None,
+        }
+    }
+
+    pub fn next(&self) -> Antialiasing {
+        match self {
+            Antialiasing::Off => Antialiasing::Msaa(4),
+            Antialiasing::Msaa(4) => Antialiasing::Msaa(8),
+            Antialiasing::Msaa(_) => Antialiasing::Ssaa(2, Downsample::Box),
+            Antialiasing::Ssaa(2, Downsample::Box) => Antialiasing::Ssaa(2, Downsample::Lanczos),
+            Antialiasing::Ssaa(2, Downsample::Lanczos) => Antialiasing::Ssaa(3, Downsample::Lanczos),
+            Antialiasing::Ssaa(_, _) => Antialiasing::Off,
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Antialiasing::Off => String::from("off"),
+            Antialiasing::Msaa(samples) => format!("MSAA {}x", samples),
+            Antialiasing::Ssaa(factor, Downsample::Box) => format!("SSAA {}x, box", factor),
+            Antialiasing::Ssaa(factor, Downsample::Lanczos) => format!("SSAA {}x, Lanczos", factor),
+        }
+    }
+
+    /// Where the samples of a pixel are, relative to its top-left corner. Without
+    /// antialiasing this is the single point the rasterizer always tested.
+    pub fn sample_offsets(&self) -> Vec<Vec2> {
+        let pattern: Vec<(f32, f32)> = match self {
+            Antialiasing::Off => vec![(0.0, 0.5)],
+            Antialiasing::Msaa(2) => MSAA_2X.to_vec(),
+            Antialiasing::Msaa(8) => MSAA_8X.to_vec(),
+            Antialiasing::Msaa(_) => MSAA_4X.to_vec(),
+            // Row by row, so sample j * factor + i sits at column i, row j of the pixel
+            Antialiasing::Ssaa(factor, _) => (0..*factor * *factor)
+                .map(|sample| {
+                    let (i, j) = (sample % factor, sample / factor);
+                    ((i as f32 + 0.5) / *factor as f32, (j as f32 + 0.5) / *factor as f32)
+                })
+                .collect(),
+        };
+        pattern.into_iter().map(|(x, y)| Vec2::new(x, y)).collect()
+    }
+
+    pub fn shades_per_sample(&self) -> bool {
+        !matches!(self, Antialiasing::Msaa(_))
+    }
+}
+
+// Average of the samples of one pixel
+pub fn box_resolve(samples: &[[f32; 3]]) -> [f32; 3] {
+    let mut sum = [0.0f32; 3];
+    for sample in samples {
+        for (total, value) in sum.iter_mut().zip(sample) {
+            *total += value;
+        }
+    }
+    sum.map(|total| total / samples.len() as f32)
+}
+
+fn lanczos(x: f32) -> f32 {
+    if x.abs() < 1e-6 {
+        1.0
+    } else if x.abs() >= LANCZOS_LOBES {
+        0.0
+    } else {
+        let x = x * PI;
+        LANCZOS_LOBES * x.sin() * (x / LANCZOS_LOBES).sin() / (x * x)
+    }
+}
+
+// Lanczos weights of the high-resolution samples that land on each output pixel.
+// Every output pixel sees the same pattern, so it is worked out once.
+fn lanczos_weights(factor: usize) -> Vec<(isize, f32)> {
+    let reach = (LANCZOS_LOBES * factor as f32).ceil() as isize;
+    let mut weights = Vec::new();
+    for offset in -reach..factor as isize + reach {
+        // Distance from the pixel center, in output pixels
+        let distance = (offset as f32 + 0.5) / factor as f32 - 0.5;
+        let weight = lanczos(distance);
+        if weight != 0.0 {
+            weights.push((offset, weight));
+        }
+    }
+    let total: f32 = weights.iter().map(|(_, weight)| weight).sum();
+    weights.into_iter().map(|(offset, weight)| (offset, weight / total)).collect()
+}
+
+/// Shrinks a `width * factor` by `height * factor` image to `width` by `height` with a
+/// separable Lanczos filter. `sample(x, y)` reads the high-resolution image.
+pub fn lanczos_downsample<F>(width: usize, height: usize, factor: usize, sample: F) -> Vec<[f32; 3]>
+where
+    F: Fn(usize, usize) -> [f32; 3],
+{
+    let weights = lanczos_weights(factor);
+    let (high_width, high_height) = (width * factor, height * factor);
+    let clamp = |value: isize, size: usize| value.clamp(0, size as isize - 1) as usize;
+
+    // Horizontal pass: full height, output width
+    let mut rows = vec![[0.0f32; 3]; width * high_height];
+    for y in 0..high_height {
+        for x in 0..width {
+            let mut sum = [0.0f32; 3];
+            for &(offset, weight) in &weights {
+                let color = sample(clamp((x * factor) as isize + offset, high_width), y);
+                for (total, value) in sum.iter_mut().zip(color) {
+                    *total += value * weight;
+                }
+            }
+            rows[y * width + x] = sum;
+        }
+    }
+
+    // Vertical pass; the negative lobes can dip below black, so clamp
+    let mut output = vec![[0.0f32; 3]; width * height];
+    for y in 0..height {
+        for x in 0..width {
+            let mut sum = [0.0f32; 3];
+            for &(offset, weight) in &weights {
+                let row = rows[clamp((y * factor) as isize + offset, high_height) * width + x];
+                for (total, value) in sum.iter_mut().zip(row) {
+                    *total += value * weight;
+                }
+            }
+            output[y * width + x] = sum.map(|value| value.max(0.0));
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_every_mode() {
+        assert!(Antialiasing::parse("off") == Some(Antialiasing::Off));
+        assert!(Antialiasing::parse("msaa4") == Some(Antialiasing::Msaa(4)));
+        assert!(Antialiasing::parse("msaa8-box") == Some(Antialiasing::Msaa(8)));
+        assert!(Antialiasing::parse("ssaa2") == Some(Antialiasing::Ssaa(2, Downsample::Box)));
+        assert!(Antialiasing::parse("ssaa3-lanczos") == Some(Antialiasing::Ssaa(3, Downsample::Lanczos)));
+    }
+
+    #[test]
+    fn rejects_unknown_modes() {
+        for name in ["", "msaa3", "msaa16", "msaa4-lanczos", "ssaa1", "ssaa5", "ssaa", "fxaa", "ssaa2-bicubic"] {
+            assert!(Antialiasing::parse(name).is_none(), "'{}' was accepted", name);
+        }
+    }
+
+    #[test]
+    fn every_mode_has_a_sample_per_coverage_bit() {
+        let mut mode = Antialiasing::Off;
+        loop {
+            let count = mode.sample_offsets().len();
+            assert!((1..=32).contains(&count), "{} has {} samples", mode.name(), count);
+            mode = mode.next();
+            if mode == Antialiasing::Off {
+                break;
+            }
+        }
+    }
+
+    #[test]
+    fn lanczos_is_one_at_the_center_and_zero_on_other_integers() {
+        assert_eq!(lanczos(0.0), 1.0);
+        for x in [1.0, -1.0, 2.0, 3.0] {
+            assert!(lanczos(x).abs() < 1e-6, "lanczos({}) = {}", x, lanczos(x));
+        }
+    }
+
+    #[test]
+    fn lanczos_weights_are_normalized_and_symmetric() {
+        for factor in 2..=4 {
+            let weights = lanczos_weights(factor);
+            let total: f32 = weights.iter().map(|(_, weight)| weight).sum();
+            assert!((total - 1.0).abs() < 1e-5, "factor {} sums to {}", factor, total);
+
+            // Offset o and factor - 1 - o are the same distance from the pixel center
+            for &(offset, weight) in &weights {
+                let mirror = factor as isize - 1 - offset;
+                let (_, mirrored) = weights.iter().find(|(other, _)| *other == mirror).expect("mirrored sample");
+                assert!((weight - mirrored).abs() < 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn downsampling_keeps_flat_images_flat() {
+        let gray = [0.25, 0.5, 0.75];
+        assert_eq!(box_resolve(&[gray; 4]), gray);
+        for pixel in lanczos_downsample(5, 3, 3, |_, _| gray) {
+            for (value, expected) in pixel.iter().zip(gray) {
+                assert!((value - expected).abs() < 1e-5);
+            }
+        }
+    }
+}
@@ -0,0 +1,268 @@
+use nalgebra_glm::{Mat4, Vec3, Vec4};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::f32::consts::PI;
+use crate::framebuffer::Framebuffer;
+use crate::panorama::{self, CubeFace};
+use crate::star_catalog;
+use crate::file_format::invalid_data;
+
+const STAR_COUNT: usize = 3000;
+// Random stars fill in below the catalog, so none of them outshine it
+const BRIGHTEST_RANDOM_MAGNITUDE: f32 = 3.5;
+const FAINTEST_RANDOM_MAGNITUDE: f32 = 6.5;
+
+pub struct Star {
+    pub direction: Vec3,
+    pub magnitude: f32,
+    pub color: u32,
+}
+
+/// What is drawn behind the bodies. Everything is at infinity, so only the
+/// camera rotation affects it.
+pub enum Background {
+    Flat,
+    Starfield(Vec<Star>),
+    Equirectangular(image::RgbImage),
+    Cubemap(Vec<CubeFace>),
+}
+
+// Approximate blackbody color of a star, as 0xRRGGBB
+pub fn temperature_to_color(kelvin: f32) -> u32 {
+    let t = kelvin / 100.0;
+
+    let r = if t <= 66.0 { 255.0 } else { 329.7 * (t - 60.0).powf(-0.1332) };
+    let g = if t <= 66.0 { 99.47 * t.ln() - 161.12 } else { 288.12 * (t - 60.0).powf(-0.0755) };
+    let b = if t >= 66.0 { 255.0 } else if t <= 19.0 { 0.0 } else { 138.52 * (t - 10.0).ln() - 305.04 };
+
+    let channel = |value: f32| value.clamp(0.0, 255.0) as u32;
+    (channel(r) << 16) | (channel(g) << 8) | channel(b)
+}
+
+// Scales every channel, for fainter stars
+pub fn scale_color(color: u32, factor: f32) -> u32 {
+    let channel = |shift: u32| ((((color >> shift) & 0xFF) as f32 * factor).clamp(0.0, 255.0) as u32) << shift;
+    channel(16) | channel(8) | channel(0)
+}
+
+// Brightness relative to the brightest stars drawn at full intensity
+pub fn magnitude_to_brightness(magnitude: f32) -> f32 {
+    10f32.powf(-0.4 * (magnitude - 1.0)).clamp(0.2, 1.0)
+}
+
+fn is_perspective(projection_matrix: &Mat4) -> bool {
+    projection_matrix[(3, 2)] != 0.0
+}
+
+// Drop the camera position so the background stays at infinity
+fn rotation_only(view_matrix: &Mat4) -> Mat4 {
+    let mut rotation = *view_matrix;
+    rotation[(0, 3)] = 0.0;
+    rotation[(1, 3)] = 0.0;
+    rotation[(2, 3)] = 0.0;
+    rotation
+}
+
+/// Projection for the sky of a `width` x `height` view. Orthographic views use a 45°
+/// perspective for the sky, since directions have no position on screen otherwise.
+pub fn sky_projection(projection_matrix: &Mat4, width: f32, height: f32) -> Mat4 {
+    if is_perspective(projection_matrix) {
+        *projection_matrix
+    } else {
+        super::create_perspective_matrix(width, height, PI / 4.0)
+    }
+}
+
+// Projection for directions at infinity
+pub fn sky_view_projection(view_matrix: &Mat4, sky_projection_matrix: &Mat4) -> Mat4 {
+    sky_projection_matrix * rotation_only(view_matrix)
+}
+
+// Screen position of a direction on the sky, or None if it is behind the camera
+pub fn project_direction(view_projection: &Mat4, viewport_matrix: &Mat4, direction: Vec3) -> Option<(f32, f32)> {
+    let clip = view_projection * Vec4::new(direction.x, direction.y, direction.z, 0.0);
+    if clip.w <= 0.0 {
+        return None;
+    }
+    let screen = viewport_matrix * Vec4::new(clip.x / clip.w, clip.y / clip.w, 0.0, 1.0);
+    Some((screen.x, screen.y))
+}
+
+impl Background {
+    /// The bright star catalog plus random faint stars, the same for the same seed.
+    /// Fainter stars are far more common, and hotter stars are rarer, roughly like the real sky.
+    pub fn starfield(seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut stars: Vec<Star> = (0..STAR_COUNT)
+            .map(|_| {
+                // Uniform on the sphere
+                let z: f32 = rng.gen_range(-1.0..1.0);
+                let angle: f32 = rng.gen_range(0.0..2.0 * PI);
+                let radius = (1.0 - z * z).sqrt();
+                let direction = Vec3::new(radius * angle.cos(), z, radius * angle.sin());
+
+                let magnitude = FAINTEST_RANDOM_MAGNITUDE - (FAINTEST_RANDOM_MAGNITUDE - BRIGHTEST_RANDOM_MAGNITUDE) * (1.0 - rng.gen::<f32>().powf(0.3));
+                let temperature = 3000.0 + 27000.0 * rng.gen::<f32>().powi(3);
+
+                Star { direction, magnitude, color: temperature_to_color(temperature) }
+            })
+            .collect();
+        stars.extend(star_catalog::catalog_stars());
+
+        Background::Starfield(stars)
+    }
+
+    // Color the viewport is cleared to before the background is drawn
+    pub fn clear_color(&self) -> u32 {
+        match self {
+            Background::Flat => 0x333355,
+            _ => 0x020208, // Near black so faint stars stay visible
+        }
+    }
+
+    pub fn load_equirectangular(filename: &str) -> image::ImageResult<Self> {
+        Ok(Background::Equirectangular(image::open(filename)?.to_rgb8()))
+    }
+
+    /// Loads `<stem>_px.png`, `<stem>_nx.png`, ... as written by the panorama export.
+    pub fn load_cubemap(stem: &str) -> image::ImageResult<Self> {
+        let mut faces = panorama::empty_faces(1);
+        let mut face_size = None;
+        for face in faces.iter_mut() {
+            let filename = format!("{}_{}.png", stem, face.name);
+            let image = image::open(&filename)?.to_rgb8();
+
+            // Sampling assumes square faces, all of the same size
+            let size = image.width();
+            if size == 0 || image.height() != size || face_size.is_some_and(|face_size| face_size != size) {
+                let message = format!("{}: cube faces must be square and all the same size, found {}x{}", filename, image.width(), image.height());
+                return Err(invalid_data(message).into());
+            }
+            face_size = Some(size);
+
+            let mut framebuffer = Framebuffer::new(image.width() as usize, image.height() as usize);
+            for (target, pixel) in framebuffer.buffer.iter_mut().zip(image.pixels()) {
+                *target = ((pixel[0] as u32) << 16) | ((pixel[1] as u32) << 8) | pixel[2] as u32;
+            }
+            face.framebuffer = framebuffer;
+        }
+        Ok(Background::Cubemap(faces))
+    }
+
+    /// Draws behind everything in the current viewport.
+    pub fn render(&self, framebuffer: &mut Framebuffer, view_matrix: &Mat4, sky_projection_matrix: &Mat4, viewport_matrix: &Mat4) {
+        if let Background::Flat = self {
+            return;
+        }
+
+        let width = 2.0 * viewport_matrix[(0, 0)];
+        let height = -2.0 * viewport_matrix[(1, 1)];
+        let view_projection = sky_view_projection(view_matrix, sky_projection_matrix);
+
+        framebuffer.set_current_id(None);
+
+        match self {
+            Background::Flat => {}
+            Background::Starfield(stars) => {
+                for star in stars {
+                    let Some((screen_x, screen_y)) = project_direction(&view_projection, viewport_matrix, star.direction) else {
+                        continue;
+                    };
+                    if screen_x < 0.0 || screen_y < 0.0 {
+                        continue;
+                    }
+
+                    let brightness = magnitude_to_brightness(star.magnitude);
+                    framebuffer.set_current_color(scale_color(star.color, brightness));
+                    let (x, y) = (screen_x as usize, screen_y as usize);
+                    framebuffer.point(x, y, f32::MAX);
+
+                    // The brightest stars get a small cross
+                    if star.magnitude < 1.0 {
+                        framebuffer.set_current_color(scale_color(star.color, brightness * 0.5));
+                        framebuffer.point(x + 1, y, f32::MAX);
+                        framebuffer.point(x, y + 1, f32::MAX);
+                        if x > 0 { framebuffer.point(x - 1, y, f32::MAX); }
+                        if y > 0 { framebuffer.point(x, y - 1, f32::MAX); }
+                    }
+                }
+            }
+            Background::Equirectangular(_) | Background::Cubemap(_) => {
+                let Some(inverse) = (viewport_matrix * view_projection).try_inverse() else {
+                    return;
+                };
+                let x_start = (viewport_matrix[(0, 3)] - width / 2.0).max(0.0) as usize;
+                let y_start = (viewport_matrix[(1, 3)] - height / 2.0).max(0.0) as usize;
+                let x_end = ((x_start as f32 + width) as usize).min(framebuffer.width);
+                let y_end = ((y_start as f32 + height) as usize).min(framebuffer.height);
+
+                for y in y_start..y_end {
+                    for x in x_start..x_end {
+                        let far = inverse * Vec4::new(x as f32 + 0.5, y as f32 + 0.5, 1.0, 1.0);
+                        let direction = (far.xyz() / far.w).normalize();
+                        framebuffer.set_current_color(self.sample(direction));
+                        framebuffer.point(x, y, f32::MAX);
+                    }
+                }
+            }
+        }
+    }
+
+    fn sample(&self, direction: Vec3) -> u32 {
+        match self {
+            Background::Equirectangular(image) => {
+                // Longitude 0 faces -Z
+                let longitude = direction.x.atan2(-direction.z);
+                let latitude = direction.y.clamp(-1.0, 1.0).asin();
+                let u = (longitude / (2.0 * PI) + 0.5) * image.width() as f32;
+                let v = (0.5 - latitude / PI) * image.height() as f32;
+                let pixel = image.get_pixel(
+                    (u as u32).min(image.width() - 1),
+                    (v as u32).min(image.height() - 1),
+                );
+                ((pixel[0] as u32) << 16) | ((pixel[1] as u32) << 8) | pixel[2] as u32
+            }
+            Background::Cubemap(faces) => panorama::sample_cubemap(faces, direction),
+            _ => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stars(background: &Background) -> &[Star] {
+        match background {
+            Background::Starfield(stars) => stars,
+            _ => panic!("not a starfield"),
+        }
+    }
+
+    #[test]
+    fn starfield_is_the_same_for_the_same_seed() {
+        let (first, again, other) = (Background::starfield(7), Background::starfield(7), Background::starfield(8));
+        let same = |a: &Star, b: &Star| a.direction == b.direction && a.magnitude == b.magnitude && a.color == b.color;
+
+        assert_eq!(stars(&first).len(), stars(&again).len());
+        assert!(stars(&first).iter().zip(stars(&again)).all(|(a, b)| same(a, b)));
+        assert!(!stars(&first).iter().zip(stars(&other)).all(|(a, b)| same(a, b)));
+
+        for star in &stars(&first)[..STAR_COUNT] {
+            assert!((star.direction.magnitude() - 1.0).abs() < 1e-5);
+            assert!((BRIGHTEST_RANDOM_MAGNITUDE..=FAINTEST_RANDOM_MAGNITUDE).contains(&star.magnitude));
+        }
+    }
+
+    #[test]
+    fn hotter_stars_are_bluer() {
+        let channels = |color: u32| ((color >> 16) & 0xFF, (color >> 8) & 0xFF, color & 0xFF);
+        let (red, _, blue) = channels(temperature_to_color(3000.0));
+        assert!(red == 255 && blue < red / 2);
+        let (red, _, blue) = channels(temperature_to_color(30000.0));
+        assert!(blue == 255 && red < blue);
+        // The sun is close to white
+        let (red, green, blue) = channels(temperature_to_color(5800.0));
+        assert!(red == 255 && green > 220 && blue > 200);
+    }
+}
@@ -0,0 +1,265 @@
+const LEVELS: usize = 5;
+// One side of a 9-tap Gaussian, center first
+const KERNEL: [f32; 5] = [0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216];
+
+// Full-size pixels per pixel of a level; the chain starts at a quarter of the size
+fn level_scale(level: usize) -> usize {
+    4 << level
+}
+
+/// Glow around emissive bodies and anything brighter than the threshold.
+#[derive(Clone, Copy)]
+pub struct Bloom {
+    pub enabled: bool,
+    pub threshold: f32, // Linear light above which surfaces start to glow
+    pub intensity: f32,
+}
+
+// One level of the mip chain, in linear light
+struct Level {
+    width: usize,
+    height: usize,
+    pixels: Vec<[f32; 3]>,
+}
+
+impl Level {
+    fn get(&self, x: isize, y: isize) -> [f32; 3] {
+        let x = x.clamp(0, self.width as isize - 1) as usize;
+        let y = y.clamp(0, self.height as isize - 1) as usize;
+        self.pixels[y * self.width + x]
+    }
+
+    // Half the size, each pixel the average of the 2x2 block under it
+    fn downsample(&self) -> Level {
+        let (width, height) = ((self.width / 2).max(1), (self.height / 2).max(1));
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in 0..height as isize {
+            for x in 0..width as isize {
+                let block = [self.get(2 * x, 2 * y), self.get(2 * x + 1, 2 * y), self.get(2 * x, 2 * y + 1), self.get(2 * x + 1, 2 * y + 1)];
+                pixels.push([0, 1, 2].map(|channel| block.iter().map(|pixel| pixel[channel]).sum::<f32>() / 4.0));
+            }
+        }
+        Level { width, height, pixels }
+    }
+
+    // Separable Gaussian, one axis at a time
+    fn blur(&self) -> Level {
+        let pass = |level: &Level, (step_x, step_y): (isize, isize)| {
+            let mut pixels = Vec::with_capacity(level.pixels.len());
+            for y in 0..level.height as isize {
+                for x in 0..level.width as isize {
+                    let mut sum = level.get(x, y).map(|value| value * KERNEL[0]);
+                    for (tap, weight) in KERNEL.iter().enumerate().skip(1) {
+                        let tap = tap as isize;
+                        let (before, after) = (level.get(x - tap * step_x, y - tap * step_y), level.get(x + tap * step_x, y + tap * step_y));
+                        for ((total, before), after) in sum.iter_mut().zip(before).zip(after) {
+                            *total += (before + after) * weight;
+                        }
+                    }
+                    pixels.push(sum);
+                }
+            }
+            Level { width: level.width, height: level.height, pixels }
+        };
+        pass(&pass(self, (1, 0)), (0, 1))
+    }
+
+    // Bilinear resize to the size of a larger level
+    fn upsample(&self, width: usize, height: usize) -> Level {
+        // The two source pixels and the weight of the second, worked out once per column and row
+        let taps = |size: usize, source_size: usize| -> Vec<(usize, usize, f32)> {
+            let scale = source_size as f32 / size as f32;
+            (0..size)
+                .map(|index| {
+                    let source = ((index as f32 + 0.5) * scale - 0.5).max(0.0);
+                    let first = (source as usize).min(source_size - 1);
+                    (first, (first + 1).min(source_size - 1), source.fract())
+                })
+                .collect()
+        };
+        let (columns, rows) = (taps(width, self.width), taps(height, self.height));
+
+        let mut pixels = Vec::with_capacity(width * height);
+        for &(y0, y1, ty) in &rows {
+            let (top_row, bottom_row) = (&self.pixels[y0 * self.width..], &self.pixels[y1 * self.width..]);
+            for &(x0, x1, tx) in &columns {
+                let (a, b, c, d) = (top_row[x0], top_row[x1], bottom_row[x0], bottom_row[x1]);
+                pixels.push([0, 1, 2].map(|channel| {
+                    let top = a[channel] + (b[channel] - a[channel]) * tx;
+                    let bottom = c[channel] + (d[channel] - c[channel]) * tx;
+                    top + (bottom - top) * ty
+                }));
+            }
+        }
+        Level { width, height, pixels }
+    }
+}
+
+impl Bloom {
+    pub fn new() -> Self {
+        Bloom { enabled: true, threshold: 1.0, intensity: 0.5 }
+    }
+
+    /// How far the glow spreads, in pixels. Every level blurs the blurred level above it,
+    /// so the blurs add up, to a little less than twice the blur of the smallest level.
+    /// Resampling leaves a tail past that, far too faint to show up in 8-bit output.
+    pub fn reach(&self) -> usize {
+        if !self.enabled || self.intensity <= 0.0 {
+            return 0;
+        }
+        level_scale(LEVELS - 1) * 2 * (KERNEL.len() - 1)
+    }
+
+    // Bright pass: what emits light, plus whatever is too bright for the screen
+    fn bright_pass(&self, light: &[[f32; 3]], emission: &[[f32; 3]]) -> Vec<[f32; 3]> {
+        light
+            .iter()
+            .zip(emission)
+            .map(|(light, emission)| [0, 1, 2].map(|channel| emission[channel] + (light[channel] - self.threshold).max(0.0)))
+            .collect()
+    }
+
+    /// Adds the glow to the linear `light` of a `width` x `height` image, before tone mapping.
+    pub fn apply(&self, light: &mut [[f32; 3]], emission: &[[f32; 3]], width: usize, height: usize) {
+        if let Some(glow) = self.glow(light, emission, width, height) {
+            for (pixel, added) in light.iter_mut().zip(glow) {
+                for (channel, added) in pixel.iter_mut().zip(added) {
+                    *channel += added;
+                }
+            }
+        }
+    }
+
+    /// The light `apply` would add to every pixel, or `None` if nothing glows.
+    pub fn glow(&self, light: &[[f32; 3]], emission: &[[f32; 3]], width: usize, height: usize) -> Option<Vec<[f32; 3]>> {
+        if !self.enabled || self.intensity <= 0.0 {
+            return None;
+        }
+
+        let pixels = self.bright_pass(light, emission);
+        if pixels.iter().all(|pixel| pixel.iter().all(|&channel| channel <= 0.0)) {
+            return None;
+        }
+        let bright = Level { width, height, pixels };
+
+        // The glow is soft anyway, so the chain starts at a quarter of the size. Each level
+        // is half the size of the one above, so the same blur spreads twice as far.
+        let mut levels = vec![bright.downsample().downsample().blur()];
+        while levels.len() < LEVELS {
+            let last = levels.last().unwrap();
+            if last.width < 4 || last.height < 4 {
+                break;
+            }
+            levels.push(last.downsample().blur());
+        }
+
+        // Walk back up the chain, adding every level into the next larger one. Each level
+        // holds all the light again, so the sum is averaged at the end.
+        let level_count = levels.len() as f32;
+        let mut glow = levels.pop().unwrap();
+        while let Some(mut level) = levels.pop() {
+            let larger = glow.upsample(level.width, level.height);
+            for (pixel, added) in level.pixels.iter_mut().zip(larger.pixels) {
+                for (channel, added) in pixel.iter_mut().zip(added) {
+                    *channel += added;
+                }
+            }
+            glow = level;
+        }
+
+        let scale = self.intensity / level_count;
+        Some(glow.upsample(width, height).pixels.into_iter().map(|pixel| pixel.map(|channel| channel * scale)).collect())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sum(pixels: &[[f32; 3]]) -> f32 {
+        pixels.iter().map(|pixel| pixel[0]).sum()
+    }
+
+    // A `size` x `size` image with one emissive pixel of `power` at (x, y)
+    fn impulse(size: usize, x: usize, y: usize, power: f32) -> (Vec<[f32; 3]>, Vec<[f32; 3]>) {
+        let mut emission = vec![[0.0; 3]; size * size];
+        emission[y * size + x] = [power; 3];
+        (vec![[0.0; 3]; size * size], emission)
+    }
+
+    #[test]
+    fn nothing_below_the_threshold_glows() {
+        let bloom = Bloom::new();
+        let light = vec![[0.0; 3], [0.5; 3], [bloom.threshold; 3], [bloom.threshold + 0.25, 0.2, bloom.threshold]];
+        let emission = vec![[0.0; 3]; light.len()];
+
+        let bright = bloom.bright_pass(&light, &emission);
+        assert_eq!(bright[..3], [[0.0; 3]; 3]);
+        assert!((bright[3][0] - 0.25).abs() < 1e-6);
+        assert_eq!(bright[3][1..], [0.0, 0.0]);
+
+        // A dim image is left exactly as it was
+        let mut image = vec![[0.9, 0.5, 0.1]; 64 * 64];
+        bloom.apply(&mut image, &vec![[0.0; 3]; 64 * 64], 64, 64);
+        assert!(image.iter().all(|pixel| *pixel == [0.9, 0.5, 0.1]));
+    }
+
+    #[test]
+    fn blur_keeps_the_total_light() {
+        let mut pixels = vec![[0.0; 3]; 32 * 32];
+        pixels[16 * 32 + 16] = [1.0; 3];
+        let blurred = Level { width: 32, height: 32, pixels }.blur();
+
+        assert!((sum(&blurred.pixels) - 1.0).abs() < 1e-5);
+        assert!(blurred.pixels[16 * 32 + 16][0] < 0.1, "the light should spread out");
+    }
+
+    #[test]
+    fn glow_adds_the_intensity_times_the_emitted_light() {
+        let bloom = Bloom::new();
+        // Big enough that the smallest level doesn't reach the edges
+        let size = 1024;
+        let (mut light, emission) = impulse(size, 512, 512, 100.0);
+        bloom.apply(&mut light, &emission, size, size);
+
+        let added = sum(&light);
+        assert!((added - 100.0 * bloom.intensity).abs() < 0.5, "added {}", added);
+        // Brightest at the source, fading away from it
+        let row = &light[512 * size..513 * size];
+        assert!(row[512][0] > row[530][0] && row[530][0] > row[600][0] && row[600][0] > 0.0);
+    }
+
+    #[test]
+    fn reach_covers_the_blur_of_every_level() {
+        let reach = Bloom::new().reach();
+        // Each level blurs the one above it, so its spread includes theirs
+        let mut spread = 0;
+        for level in 0..LEVELS {
+            spread += level_scale(level) * (KERNEL.len() - 1);
+            assert!(spread <= reach, "level {}: {} > {}", level, spread, reach);
+        }
+        // Tiles padded by the reach keep the downsampling grid of the whole image
+        assert_eq!(reach % level_scale(LEVELS - 1), 0);
+
+        assert_eq!(Bloom { enabled: false, ..Bloom::new() }.reach(), 0);
+        assert_eq!(Bloom { intensity: 0.0, ..Bloom::new() }.reach(), 0);
+    }
+
+    #[test]
+    fn no_visible_glow_beyond_the_reach() {
+        let bloom = Bloom::new();
+        let reach = bloom.reach();
+        let size = 2 * reach + 128;
+        let (x, y) = (size / 2 + 3, size / 2 - 5);
+        let (mut light, emission) = impulse(size, x, y, 1000.0);
+        bloom.apply(&mut light, &emission, size, size);
+
+        for (index, pixel) in light.iter().enumerate() {
+            let distance = (index % size).abs_diff(x).max((index / size).abs_diff(y));
+            if distance > reach {
+                assert!(pixel[0] < 1000.0 * 1e-9, "glow of {} {} pixels away", pixel[0], distance);
+            }
+        }
+    }
+}
@@ -0,0 +1,269 @@
+use nalgebra_glm::Vec3;
+use std::f32::consts::PI;
+use std::fs;
+use std::io;
+use crate::camera::{Camera, CameraMode};
+use crate::projection::ProjectionKind;
+use crate::warp::Obstacle;
+use crate::file_format::invalid_data;
+
+#[derive(Clone)]
+pub struct Bookmark {
+    pub name: String,
+    pub eye: Vec3,
+    pub center: Vec3,
+    pub up: Vec3,
+    pub mode: CameraMode,
+    pub projection: ProjectionKind,
+    pub fov: f32,
+    pub ortho_height: f32,
+    pub time: u32,
+}
+
+pub struct Bookmarks {
+    pub entries: Vec<Bookmark>,
+}
+
+fn mode_name(mode: CameraMode) -> &'static str {
+    match mode {
+        CameraMode::Normal => "normal",
+        CameraMode::BirdsEye => "birds-eye",
+        CameraMode::Flight => "flight",
+    }
+}
+
+fn parse_mode(name: &str) -> Option<CameraMode> {
+    match name {
+        "normal" => Some(CameraMode::Normal),
+        "birds-eye" => Some(CameraMode::BirdsEye),
+        "flight" => Some(CameraMode::Flight),
+        _ => None,
+    }
+}
+
+fn projection_name(kind: ProjectionKind) -> &'static str {
+    match kind {
+        ProjectionKind::Perspective => "perspective",
+        ProjectionKind::Orthographic => "orthographic",
+    }
+}
+
+fn parse_projection(name: &str) -> Option<ProjectionKind> {
+    match name {
+        "perspective" => Some(ProjectionKind::Perspective),
+        "orthographic" => Some(ProjectionKind::Orthographic),
+        _ => None,
+    }
+}
+
+impl Bookmark {
+    pub fn from_camera(name: &str, camera: &Camera, time: u32) -> Self {
+        Bookmark {
+            name: name.to_string(),
+            eye: camera.eye,
+            center: camera.center,
+            up: camera.up,
+            mode: camera.mode,
+            projection: camera.projection.kind,
+            fov: camera.projection.fov,
+            ortho_height: camera.projection.ortho_height,
+            time,
+        }
+    }
+
+    // Restores mode and projection right away and warps to the saved framing
+    pub fn recall(&self, camera: &mut Camera, obstacles: &[Obstacle]) {
+        camera.mode = self.mode;
+        camera.projection.kind = self.projection;
+        camera.projection.set_fov(self.fov);
+        camera.projection.ortho_height = self.ortho_height;
+        camera.start_warp_around(self.eye, self.center, obstacles);
+        camera.warp_up_to(self.up);
+    }
+
+    // Jumps straight to the bookmark instead of warping, for the starting view
+    pub fn apply(&self, camera: &mut Camera) {
+        camera.mode = self.mode;
+        camera.projection.kind = self.projection;
+        camera.projection.set_fov(self.fov);
+        camera.projection.ortho_height = self.ortho_height;
+        camera.set_view(self.eye, self.center, self.up);
+    }
+
+    fn to_line(&self) -> String {
+        format!(
+            "{} {} {} {} {} {} {} {} {} {} {} {} {} {} {}",
+            self.eye.x, self.eye.y, self.eye.z,
+            self.center.x, self.center.y, self.center.z,
+            self.up.x, self.up.y, self.up.z,
+            mode_name(self.mode),
+            projection_name(self.projection),
+            self.fov * 180.0 / PI,
+            self.ortho_height,
+            self.time,
+            self.name,
+        )
+    }
+
+    fn from_line(line: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = line.splitn(15, ' ').collect();
+        if fields.len() != 15 {
+            return Err(format!("expected 15 fields, found {}", fields.len()));
+        }
+
+        let number = |index: usize| -> Result<f32, String> {
+            fields[index].parse::<f32>().map_err(|e| format!("field {}: {}", index + 1, e))
+        };
+
+        Ok(Bookmark {
+            eye: Vec3::new(number(0)?, number(1)?, number(2)?),
+            center: Vec3::new(number(3)?, number(4)?, number(5)?),
+            up: Vec3::new(number(6)?, number(7)?, number(8)?),
+            mode: parse_mode(fields[9]).ok_or(format!("unknown camera mode '{}'", fields[9]))?,
+            projection: parse_projection(fields[10]).ok_or(format!("unknown projection '{}'", fields[10]))?,
+            fov: number(11)? * PI / 180.0,
+            ortho_height: number(12)?,
+            time: fields[13].parse::<u32>().map_err(|e| format!("field 14: {}", e))?,
+            name: fields[14].to_string(),
+        })
+    }
+}
+
+impl Bookmarks {
+    pub fn new() -> Self {
+        Bookmarks { entries: Vec::new() }
+    }
+
+    // Replaces any bookmark with the same name
+    pub fn add(&mut self, bookmark: Bookmark) {
+        match self.entries.iter_mut().find(|b| b.name == bookmark.name) {
+            Some(existing) => *existing = bookmark,
+            None => self.entries.push(bookmark),
+        }
+    }
+
+    // A name no bookmark has yet, so saving never replaces one: `base`, else "base 2",
+    // "base 3", ...; without a base, "bookmark N" with the first N not already taken
+    pub fn next_name(&self, base: Option<&str>) -> String {
+        let (base, first) = match base {
+            Some(base) if self.get(base).is_none() => return base.to_string(),
+            Some(base) => (base, 2),
+            None => ("bookmark", self.entries.len() + 1),
+        };
+        (first..)
+            .map(|number| format!("{} {}", base, number))
+            .find(|name| self.get(name).is_none())
+            .unwrap()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Bookmark> {
+        self.entries.iter().find(|b| b.name == name)
+    }
+
+    pub fn print(&self) {
+        if self.entries.is_empty() {
+            println!("No camera bookmarks");
+        }
+        for (index, bookmark) in self.entries.iter().enumerate() {
+            println!(
+                "F{}: {} (eye {:.1} {:.1} {:.1}, {}, t={})",
+                index + 1,
+                bookmark.name,
+                bookmark.eye.x, bookmark.eye.y, bookmark.eye.z,
+                mode_name(bookmark.mode),
+                bookmark.time,
+            );
+        }
+    }
+
+    pub fn save(&self, filename: &str) -> io::Result<()> {
+        let mut contents = String::new();
+        contents.push_str("# eye_x eye_y eye_z center_x center_y center_z up_x up_y up_z mode projection fov_degrees ortho_height time name\n");
+        for bookmark in &self.entries {
+            contents.push_str(&bookmark.to_line());
+            contents.push('\n');
+        }
+
+        fs::write(filename, contents)
+    }
+
+    pub fn load(filename: &str) -> io::Result<Self> {
+        let contents = fs::read_to_string(filename)?;
+        let mut bookmarks = Bookmarks::new();
+
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let bookmark = Bookmark::from_line(line)
+                .map_err(|e| invalid_data(format!("line {}: {}", number + 1, e)))?;
+            bookmarks.add(bookmark);
+        }
+
+        Ok(bookmarks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bookmark(name: &str) -> Bookmark {
+        let mut camera = Camera::new(Vec3::new(1.5, -2.0, 10.0), Vec3::new(0.0, 0.5, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        camera.projection.kind = ProjectionKind::Orthographic;
+        camera.projection.set_fov(PI / 5.0);
+        camera.projection.ortho_height = 12.5;
+        Bookmark::from_camera(name, &camera, 4321)
+    }
+
+    #[test]
+    fn lines_read_back_the_same() {
+        let saved = bookmark("Jupiter from below");
+        let loaded = Bookmark::from_line(&saved.to_line()).unwrap();
+
+        assert_eq!(loaded.name, "Jupiter from below");
+        assert!((loaded.eye - saved.eye).magnitude() < 1e-5);
+        assert!((loaded.center - saved.center).magnitude() < 1e-5);
+        assert!((loaded.up - saved.up).magnitude() < 1e-5);
+        assert!(loaded.mode == saved.mode);
+        assert!(loaded.projection == ProjectionKind::Orthographic);
+        assert!((loaded.fov - saved.fov).abs() < 1e-5);
+        assert_eq!(loaded.ortho_height, 12.5);
+        assert_eq!(loaded.time, 4321);
+    }
+
+    #[test]
+    fn malformed_lines_are_rejected() {
+        let good = bookmark("view").to_line();
+        let fields: Vec<&str> = good.split(' ').collect();
+        let with = |index: usize, value: &str| {
+            let mut fields = fields.clone();
+            fields[index] = value;
+            fields.join(" ")
+        };
+
+        for line in [
+            String::from("1 2 3"),
+            with(0, "x"),
+            with(9, "sideways"),
+            with(10, "fisheye"),
+            with(13, "-5"),
+        ] {
+            assert!(Bookmark::from_line(&line).is_err(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn new_names_never_replace_a_bookmark() {
+        let mut bookmarks = Bookmarks::new();
+        assert_eq!(bookmarks.next_name(None), "bookmark 1");
+        assert_eq!(bookmarks.next_name(Some("Rings")), "Rings");
+
+        bookmarks.add(bookmark("Rings"));
+        bookmarks.add(bookmark("bookmark 2"));
+        assert_eq!(bookmarks.next_name(Some("Rings")), "Rings 2");
+        assert_eq!(bookmarks.next_name(None), "bookmark 3");
+    }
+}
@@ -1,31 +1,61 @@
 use nalgebra_glm::{Vec3, rotate_vec3};
+use nalgebra::{UnitQuaternion, Vector3};
 use std::f32::consts::PI;
+use crate::warp::{self, Easing, Obstacle, WarpSettings};
+use crate::projection::{Projection, ProjectionKind};
 
+#[derive(Clone, Copy, PartialEq)]
 pub enum CameraMode {
     Normal,
     BirdsEye,
+    Flight,
 }
 
+impl CameraMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            CameraMode::Normal => "normal",
+            CameraMode::BirdsEye => "birds-eye",
+            CameraMode::Flight => "flight",
+        }
+    }
+}
+
+const FLIGHT_DAMPING: f32 = 0.2; // Fraction of velocity kept after one second without thrust
+const MIN_CENTER_DISTANCE: f32 = 0.5; // Closest the eye may zoom towards `center`
+const COLLISION_MARGIN: f32 = 0.3; // Distance kept from every body's surface
+
 pub struct Camera {
   pub eye: Vec3,
   pub center: Vec3,
   pub up: Vec3,
   pub has_changed: bool,
   pub mode: CameraMode,
+  pub projection: Projection,
+  pub warp_settings: WarpSettings,
+  pub colliders: Vec<Obstacle>, // Bodies the eye must stay outside of, refreshed every frame
 
+  // Free-flight fields, local forward is -Z
+  orientation: UnitQuaternion<f32>,
+  velocity: Vec3,
 
   // Animation fields
     anim_start_eye: Vec3,
     anim_start_center: Vec3,
+    anim_start_up: Vec3,
+    anim_control_eye: Vec3,
     anim_target_eye: Vec3,
     anim_target_center: Vec3,
+    anim_target_up: Vec3,
+    anim_easing: Easing,
     anim_progress: f32,
     anim_duration: f32,
     is_animating: bool,
 }
 
-fn smooth_step(t: f32) -> f32 {
-    t * t * (3.0 - 2.0 * t) // Smoothstep easing
+// Rotation taking the local -Z axis to the view direction
+pub fn look_orientation(eye: Vec3, center: Vec3, up: Vec3) -> UnitQuaternion<f32> {
+    UnitQuaternion::look_at_rh(&(center - eye), &up).inverse()
 }
 
 impl Camera {
@@ -36,28 +66,57 @@ impl Camera {
       up,
       has_changed: true,
       mode: CameraMode::Normal,
+      projection: Projection::new(),
+      warp_settings: WarpSettings::new(),
+      colliders: Vec::new(),
+
+      orientation: look_orientation(eye, center, up),
+      velocity: Vec3::zeros(),
 
       // Initialize animation-related fields
             anim_start_eye: eye,
             anim_start_center: center,
+            anim_start_up: up,
+            anim_control_eye: eye,
             anim_target_eye: eye,
             anim_target_center: center,
+            anim_target_up: up,
+            anim_easing: Easing::SmoothStep,
             anim_progress: 0.0,
             anim_duration: 0.0,
             is_animating: false,
     }
   }
 
-  pub fn start_warp(&mut self, target_eye: Vec3, target_center: Vec3, duration: f32) {
+  // Warps along an arc that clears every obstacle, taking longer for longer trips
+  pub fn start_warp_around(&mut self, target_eye: Vec3, target_center: Vec3, obstacles: &[Obstacle]) {
+        let control_eye = warp::plan_arc(self.eye, target_eye, obstacles);
+        let distance = warp::arc_length(self.eye, control_eye, target_eye);
+        let duration = self.warp_settings.duration_for(distance);
+        let easing = self.warp_settings.easing;
+        self.start_warp_along(target_eye, target_center, control_eye, duration, easing);
+ }
+
+  pub fn start_warp_along(&mut self, target_eye: Vec3, target_center: Vec3, control_eye: Vec3, duration: f32, easing: Easing) {
         self.anim_start_eye = self.eye;
         self.anim_start_center = self.center;
+        self.anim_start_up = self.up;
+        self.anim_control_eye = control_eye;
         self.anim_target_eye = target_eye;
         self.anim_target_center = target_center;
+        self.anim_target_up = self.up;
         self.anim_progress = 0.0;
         self.anim_duration = duration;
+        self.anim_easing = easing;
         self.is_animating = true;
+        self.velocity = Vec3::zeros();
  }
 
+  // Also turns the up vector during the warp that was just started
+  pub fn warp_up_to(&mut self, target_up: Vec3) {
+    self.anim_target_up = target_up;
+  }
+
 pub fn update(&mut self, delta_time: f32) {
         if self.is_animating {
             self.anim_progress += delta_time / self.anim_duration;
@@ -66,19 +125,41 @@ pub fn update(&mut self, delta_time: f32) {
                 self.is_animating = false;
             }
 
-            let t = smooth_step(self.anim_progress);
+            let t = self.anim_easing.apply(self.anim_progress);
 
-            self.eye = self.anim_start_eye.lerp(&self.anim_target_eye, t);
+            self.eye = warp::quadratic_bezier(self.anim_start_eye, self.anim_control_eye, self.anim_target_eye, t);
             self.center = self.anim_start_center.lerp(&self.anim_target_center, t);
+            self.up = self.anim_start_up
+                .lerp(&self.anim_target_up, t)
+                .try_normalize(1.0e-6)
+                .unwrap_or(self.anim_target_up);
+            self.resolve_collisions();
+
+            if let CameraMode::Flight = self.mode {
+                self.orientation = look_orientation(self.eye, self.center, self.up);
+            }
 
             self.has_changed = true;
+        } else if let CameraMode::Flight = self.mode {
+            self.eye += self.velocity * delta_time;
+            self.velocity *= FLIGHT_DAMPING.powf(delta_time);
+
+            // Slide along the surface instead of bouncing back into it
+            if let Some(normal) = self.resolve_collisions() {
+                let into_surface = self.velocity.dot(&normal).min(0.0);
+                self.velocity -= normal * into_surface;
+            }
+            self.sync_flight_view();
         }
     }
 
-  pub fn switch_to_birds_eye(&mut self) {
+  // `scene_radius` is how far out the bodies go, so all of their orbits fit in the view
+  pub fn switch_to_birds_eye(&mut self, scene_radius: f32) {
         self.eye = Vec3::new(0.0, 20.0, 0.0); // Set camera above the scene
         self.center = Vec3::new(0.0, 0.0, 0.0); // Look at the origin
         self.up = Vec3::new(0.0, 0.0, -1.0); // Adjust 'up' vector for downward view
+        self.projection.kind = ProjectionKind::Orthographic; // Undistorted orrery map
+        self.projection.ortho_height = (2.0 * scene_radius).max(1.0) + 2.0; // A unit of margin on each side
         self.mode = CameraMode::BirdsEye;
     }
 
@@ -86,9 +167,56 @@ pub fn update(&mut self, delta_time: f32) {
         self.eye = Vec3::new(0.0, 0.0, 20.0); // Reset to original position
         self.center = Vec3::new(0.0, 0.0, 0.0);
         self.up = Vec3::new(0.0, 1.0, 0.0);
+        self.projection.kind = ProjectionKind::Perspective;
         self.mode = CameraMode::Normal;
     }
 
+    pub fn switch_to_flight(&mut self) {
+        self.orientation = look_orientation(self.eye, self.center, self.up);
+        self.velocity = Vec3::zeros();
+        self.projection.kind = ProjectionKind::Perspective;
+        self.mode = CameraMode::Flight;
+        self.sync_flight_view();
+    }
+
+  // Places the camera directly, keeping the flight orientation in sync
+  pub fn set_view(&mut self, eye: Vec3, center: Vec3, up: Vec3) {
+    self.eye = eye;
+    self.center = center;
+    self.up = up;
+    self.orientation = look_orientation(eye, center, up);
+    self.has_changed = true;
+  }
+
+  pub fn is_flying(&self) -> bool {
+    matches!(self.mode, CameraMode::Flight)
+  }
+
+  // Rotates around the ship's own axes, so pitching while rolled stays relative to the cockpit
+  pub fn rotate_local(&mut self, delta_yaw: f32, delta_pitch: f32, delta_roll: f32) {
+    let yaw = UnitQuaternion::from_axis_angle(&Vector3::y_axis(), delta_yaw);
+    let pitch = UnitQuaternion::from_axis_angle(&Vector3::x_axis(), delta_pitch);
+    let roll = UnitQuaternion::from_axis_angle(&Vector3::z_axis(), delta_roll);
+
+    self.orientation = self.orientation * yaw * pitch * roll;
+    self.orientation.renormalize_fast();
+    self.sync_flight_view();
+  }
+
+  // Adds velocity along local axes: x strafes right, y rises, -z thrusts forward
+  pub fn thrust(&mut self, local: Vec3) {
+    self.velocity += self.orientation * local;
+  }
+
+  fn sync_flight_view(&mut self) {
+    let forward = self.orientation * Vec3::new(0.0, 0.0, -1.0);
+    let distance = (self.center - self.eye).magnitude().max(1.0);
+
+    self.center = self.eye + forward * distance;
+    self.up = self.orientation * Vec3::new(0.0, 1.0, 0.0);
+    self.has_changed = true;
+  }
+
   pub fn basis_change(&self, vector: &Vec3) -> Vec3 {
     let forward = (self.center - self.eye).normalize();
     let right = forward.cross(&self.up).normalize();
@@ -121,12 +249,79 @@ pub fn update(&mut self, delta_time: f32) {
     );
 
     self.eye = new_eye;
+    self.resolve_collisions();
     self.has_changed = true;
   }
 
   pub fn zoom(&mut self, delta: f32) {
-    let direction = (self.center - self.eye).normalize();
+    if self.projection.is_orthographic() {
+      // Moving the eye doesn't change the scale, shrink the visible area by the same ratio instead
+      let distance = (self.center - self.eye).magnitude();
+      let ratio = ((distance - delta) / distance).max(0.1);
+      self.projection.ortho_height = (self.projection.ortho_height * ratio).max(0.5);
+      self.has_changed = true;
+      return;
+    }
+
+    let offset = self.center - self.eye;
+    let distance = offset.magnitude();
+    let direction = offset.normalize();
+
+    // Never zoom through the center, which would flip the view around
+    let delta = delta.min(distance - MIN_CENTER_DISTANCE);
     self.eye += direction * delta;
+    self.resolve_collisions();
+    self.has_changed = true;
+  }
+
+  // Pushes the eye out of any body it ended up inside.
+  // Returns the surface normal at the push-out point of the last body hit.
+  fn resolve_collisions(&mut self) -> Option<Vec3> {
+    let mut normal = None;
+
+    for collider in &self.colliders {
+      let min_distance = collider.radius + COLLISION_MARGIN;
+      let offset = self.eye - collider.center;
+      let distance = offset.magnitude();
+
+      if distance < min_distance {
+        let direction = offset.try_normalize(1.0e-6).unwrap_or(Vec3::new(0.0, 1.0, 0.0));
+        self.eye = collider.center + direction * min_distance;
+        normal = Some(direction);
+      }
+    }
+
+    normal
+  }
+
+  // Keeps the view height at `center` fixed while changing the FOV, moving the eye to compensate
+  pub fn dolly_zoom(&mut self, delta_fov: f32) {
+    if self.projection.is_orthographic() {
+      return;
+    }
+
+    let offset = self.eye - self.center;
+    let view_height = self.projection.view_height_at(offset.magnitude());
+    self.projection.set_fov(self.projection.fov + delta_fov);
+
+    let distance = view_height / (2.0 * (self.projection.fov / 2.0).tan());
+    self.eye = self.center + offset.normalize() * distance;
+    self.resolve_collisions();
+    self.has_changed = true;
+  }
+
+  // Switches projection while keeping the framing at `center` the same size
+  pub fn toggle_orthographic(&mut self) {
+    let distance = (self.center - self.eye).magnitude();
+    match self.projection.kind {
+      ProjectionKind::Perspective => {
+        self.projection.ortho_height = self.projection.view_height_at(distance);
+        self.projection.kind = ProjectionKind::Orthographic;
+      }
+      ProjectionKind::Orthographic => {
+        self.projection.kind = ProjectionKind::Perspective;
+      }
+    }
     self.has_changed = true;
   }
 
@@ -145,4 +340,106 @@ pub fn update(&mut self, delta_time: f32) {
     self.center = self.eye + final_rotated.normalize() * radius;
     self.has_changed = true;
   }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).magnitude() < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    fn flying_camera() -> Camera {
+        let mut camera = Camera::new(Vec3::new(0.0, 0.0, 10.0), Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        camera.switch_to_flight();
+        camera
+    }
+
+    #[test]
+    fn look_orientation_turns_local_axes_to_the_view() {
+        let (eye, center, up) = (Vec3::new(1.0, 2.0, 3.0), Vec3::new(4.0, 2.0, -1.0), Vec3::new(0.0, 1.0, 0.0));
+        let orientation = look_orientation(eye, center, up);
+        assert_close(orientation * Vec3::new(0.0, 0.0, -1.0), (center - eye).normalize());
+        assert_close(orientation * Vec3::new(0.0, 1.0, 0.0), up);
+    }
+
+    #[test]
+    fn yaw_turns_around_the_ship_up_axis() {
+        let mut camera = flying_camera();
+        camera.rotate_local(PI / 2.0, 0.0, 0.0);
+        // A quarter turn left from looking down -Z
+        assert_close((camera.center - camera.eye).normalize(), Vec3::new(-1.0, 0.0, 0.0));
+        assert_close(camera.up, Vec3::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn pitch_after_roll_stays_relative_to_the_cockpit() {
+        let mut camera = flying_camera();
+        camera.rotate_local(0.0, 0.0, PI / 2.0);
+        camera.rotate_local(0.0, PI / 2.0, 0.0);
+        // Rolled onto the side, pulling up turns towards the world's -X
+        assert_close((camera.center - camera.eye).normalize(), Vec3::new(-1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn basis_stays_orthonormal_after_many_rotations() {
+        let mut camera = flying_camera();
+        for _ in 0..1000 {
+            camera.rotate_local(0.013, -0.007, 0.021);
+        }
+        let forward = (camera.center - camera.eye).normalize();
+        assert!((camera.up.magnitude() - 1.0).abs() < 1e-4);
+        assert!(forward.dot(&camera.up).abs() < 1e-4);
+    }
+
+    #[test]
+    fn thrust_follows_the_orientation() {
+        let mut camera = flying_camera();
+        camera.rotate_local(PI / 2.0, 0.0, 0.0);
+        camera.thrust(Vec3::new(0.0, 0.0, -1.0));
+        let start = camera.eye;
+        camera.update(0.1);
+        assert!(camera.eye.x < start.x);
+        assert!((camera.eye.z - start.z).abs() < 1e-4);
+    }
+
+    fn camera_with_body() -> Camera {
+        let mut camera = Camera::new(Vec3::new(0.0, 0.0, 10.0), Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        camera.colliders = vec![Obstacle { center: Vec3::new(0.0, 0.0, 0.0), radius: 2.0 }];
+        camera
+    }
+
+    fn assert_outside_colliders(camera: &Camera) {
+        for collider in &camera.colliders {
+            let distance = (camera.eye - collider.center).magnitude();
+            assert!(distance >= collider.radius + COLLISION_MARGIN - 1e-4, "eye {} from a body of radius {}", distance, collider.radius);
+        }
+    }
+
+    #[test]
+    fn zoom_stops_short_of_the_center() {
+        let mut camera = Camera::new(Vec3::new(0.0, 0.0, 10.0), Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        camera.zoom(50.0);
+        assert_close(camera.eye, Vec3::new(0.0, 0.0, MIN_CENTER_DISTANCE));
+        camera.zoom(50.0);
+        assert_close(camera.eye, Vec3::new(0.0, 0.0, MIN_CENTER_DISTANCE));
+    }
+
+    #[test]
+    fn zoom_orbit_and_dolly_zoom_keep_out_of_bodies() {
+        let mut camera = camera_with_body();
+        camera.zoom(9.0);
+        assert_outside_colliders(&camera);
+
+        // Orbiting around a second body that sits in the way
+        camera.colliders.push(Obstacle { center: Vec3::new(0.0, -2.3, 0.0), radius: 1.0 });
+        camera.orbit(0.0, PI / 2.0);
+        assert_outside_colliders(&camera);
+
+        // Widening the FOV pulls the eye in towards the center
+        let mut camera = camera_with_body();
+        camera.zoom(7.0);
+        camera.dolly_zoom(0.8);
+        assert_outside_colliders(&camera);
+    }
+}
@@ -0,0 +1,320 @@
+use nalgebra_glm::Vec3;
+use std::f32::consts::PI;
+use std::fs;
+use std::io;
+use crate::camera::{Camera, look_orientation};
+use crate::file_format::invalid_data;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Interpolation {
+    CatmullRom,
+    Bezier,
+}
+
+#[derive(Clone, Copy)]
+pub struct Keyframe {
+    pub time: f32,
+    pub eye: Vec3,
+    pub center: Vec3,
+    pub up: Vec3,
+    pub fov: f32,
+}
+
+pub struct CameraPath {
+    pub keyframes: Vec<Keyframe>,
+    pub interpolation: Interpolation,
+}
+
+fn catmull_rom(p0: Vec3, p1: Vec3, p2: Vec3, p3: Vec3, t: f32) -> Vec3 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+
+    (p1 * 2.0
+        + (p2 - p0) * t
+        + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2
+        + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3) * 0.5
+}
+
+// Handles follow the neighbour tangents but are scaled by the segment length,
+// which avoids the overshoot Catmull-Rom shows with unevenly spaced keyframes
+fn bezier(p0: Vec3, p1: Vec3, p2: Vec3, p3: Vec3, t: f32) -> Vec3 {
+    let length = (p2 - p1).magnitude() / 3.0;
+    let tangent_start = (p2 - p0).try_normalize(1.0e-6).unwrap_or(Vec3::zeros());
+    let tangent_end = (p3 - p1).try_normalize(1.0e-6).unwrap_or(Vec3::zeros());
+
+    let c1 = p1 + tangent_start * length;
+    let c2 = p2 - tangent_end * length;
+    let u = 1.0 - t;
+
+    p1 * (u * u * u) + c1 * (3.0 * u * u * t) + c2 * (3.0 * u * t * t) + p2 * (t * t * t)
+}
+
+impl Keyframe {
+    pub fn from_camera(camera: &Camera, time: f32) -> Self {
+        Keyframe {
+            time,
+            eye: camera.eye,
+            center: camera.center,
+            up: camera.up,
+            fov: camera.projection.fov,
+        }
+    }
+
+    pub fn apply(&self, camera: &mut Camera) {
+        camera.set_view(self.eye, self.center, self.up);
+        camera.projection.set_fov(self.fov);
+    }
+}
+
+impl CameraPath {
+    pub fn new(interpolation: Interpolation) -> Self {
+        CameraPath {
+            keyframes: Vec::new(),
+            interpolation,
+        }
+    }
+
+    // Keeps keyframes sorted by time
+    pub fn add_keyframe(&mut self, keyframe: Keyframe) {
+        let index = self.keyframes.partition_point(|k| k.time <= keyframe.time);
+        self.keyframes.insert(index, keyframe);
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |k| k.time)
+    }
+
+    /// Camera state at the given time: position along the spline, orientation slerped.
+    pub fn sample(&self, time: f32) -> Option<Keyframe> {
+        let count = self.keyframes.len();
+        let first = self.keyframes.first()?;
+        if count == 1 || time <= first.time {
+            return Some(*first);
+        }
+        if time >= self.duration() {
+            return self.keyframes.last().copied();
+        }
+
+        let i = self.keyframes.partition_point(|k| k.time <= time) - 1;
+        let k0 = &self.keyframes[i.saturating_sub(1)];
+        let k1 = &self.keyframes[i];
+        let k2 = &self.keyframes[i + 1];
+        let k3 = &self.keyframes[(i + 2).min(count - 1)];
+
+        let span = k2.time - k1.time;
+        let t = if span > 0.0 { (time - k1.time) / span } else { 1.0 };
+
+        let eye = match self.interpolation {
+            Interpolation::CatmullRom => catmull_rom(k0.eye, k1.eye, k2.eye, k3.eye, t),
+            Interpolation::Bezier => bezier(k0.eye, k1.eye, k2.eye, k3.eye, t),
+        };
+
+        let orientation = look_orientation(k1.eye, k1.center, k1.up)
+            .slerp(&look_orientation(k2.eye, k2.center, k2.up), t);
+        let distance = (k1.center - k1.eye).magnitude() * (1.0 - t) + (k2.center - k2.eye).magnitude() * t;
+
+        Some(Keyframe {
+            time,
+            eye,
+            center: eye + orientation * Vec3::new(0.0, 0.0, -distance),
+            up: orientation * Vec3::new(0.0, 1.0, 0.0),
+            fov: k1.fov + (k2.fov - k1.fov) * t,
+        })
+    }
+
+    pub fn save(&self, filename: &str) -> io::Result<()> {
+        let mut contents = String::new();
+        contents.push_str("# time eye_x eye_y eye_z center_x center_y center_z up_x up_y up_z fov_degrees\n");
+        contents.push_str(match self.interpolation {
+            Interpolation::CatmullRom => "interpolation catmull-rom\n",
+            Interpolation::Bezier => "interpolation bezier\n",
+        });
+
+        for k in &self.keyframes {
+            contents.push_str(&format!(
+                "{} {} {} {} {} {} {} {} {} {} {}\n",
+                k.time,
+                k.eye.x, k.eye.y, k.eye.z,
+                k.center.x, k.center.y, k.center.z,
+                k.up.x, k.up.y, k.up.z,
+                k.fov * 180.0 / PI,
+            ));
+        }
+
+        fs::write(filename, contents)
+    }
+
+    pub fn load(filename: &str) -> io::Result<Self> {
+        let contents = fs::read_to_string(filename)?;
+        let mut path = CameraPath::new(Interpolation::CatmullRom);
+
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(mode) = line.strip_prefix("interpolation") {
+                path.interpolation = match mode.trim() {
+                    "catmull-rom" => Interpolation::CatmullRom,
+                    "bezier" => Interpolation::Bezier,
+                    other => return Err(invalid_data(format!("line {}: unknown interpolation '{}'", number + 1, other))),
+                };
+                continue;
+            }
+
+            let values = line
+                .split_whitespace()
+                .map(|value| value.parse::<f32>())
+                .collect::<Result<Vec<f32>, _>>()
+                .map_err(|e| invalid_data(format!("line {}: {}", number + 1, e)))?;
+            if values.len() != 11 {
+                return Err(invalid_data(format!("line {}: expected 11 values, found {}", number + 1, values.len())));
+            }
+
+            path.add_keyframe(Keyframe {
+                time: values[0],
+                eye: Vec3::new(values[1], values[2], values[3]),
+                center: Vec3::new(values[4], values[5], values[6]),
+                up: Vec3::new(values[7], values[8], values[9]),
+                fov: values[10] * PI / 180.0,
+            });
+        }
+
+        Ok(path)
+    }
+}
+
+/// Records keyframes from the live camera and plays the path back.
+pub struct PathController {
+    pub path: CameraPath,
+    clock: f32,
+    playing: bool,
+}
+
+impl PathController {
+    pub fn new(path: CameraPath) -> Self {
+        PathController {
+            path,
+            clock: 0.0,
+            playing: false,
+        }
+    }
+
+    // Keyframes are spaced by the time that passed since the previous one was added
+    pub fn add_keyframe(&mut self, camera: &Camera) {
+        let time = match self.path.keyframes.last() {
+            Some(last) => last.time + self.clock.max(0.1),
+            None => 0.0,
+        };
+        self.path.add_keyframe(Keyframe::from_camera(camera, time));
+        self.clock = 0.0;
+    }
+
+    pub fn toggle_playback(&mut self) {
+        self.playing = !self.playing && self.path.keyframes.len() > 1;
+        self.clock = 0.0;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    pub fn update(&mut self, camera: &mut Camera, delta_time: f32) {
+        self.clock += delta_time;
+        if !self.playing {
+            return;
+        }
+
+        if let Some(keyframe) = self.path.sample(self.clock) {
+            keyframe.apply(camera);
+        }
+        if self.clock >= self.path.duration() {
+            self.playing = false;
+            self.clock = 0.0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).magnitude() < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    fn keyframe(time: f32, eye: Vec3) -> Keyframe {
+        Keyframe { time, eye, center: Vec3::new(0.0, 0.0, 0.0), up: Vec3::new(0.0, 1.0, 0.0), fov: PI / 3.0 }
+    }
+
+    fn path(interpolation: Interpolation) -> CameraPath {
+        let mut path = CameraPath::new(interpolation);
+        path.add_keyframe(keyframe(0.0, Vec3::new(0.0, 0.0, 10.0)));
+        path.add_keyframe(keyframe(2.0, Vec3::new(10.0, 0.0, 0.0)));
+        path.add_keyframe(keyframe(3.0, Vec3::new(0.0, 5.0, -10.0)));
+        path.add_keyframe(keyframe(6.0, Vec3::new(-10.0, 0.0, 0.0)));
+        path
+    }
+
+    #[test]
+    fn catmull_rom_passes_through_its_control_points() {
+        let points = [Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 2.0, 0.0), Vec3::new(3.0, 1.0, 1.0), Vec3::new(4.0, 4.0, 2.0)];
+        assert_close(catmull_rom(points[0], points[1], points[2], points[3], 0.0), points[1]);
+        assert_close(catmull_rom(points[0], points[1], points[2], points[3], 1.0), points[2]);
+    }
+
+    #[test]
+    fn evenly_spaced_points_give_a_straight_line() {
+        let (p0, p1, p2, p3) = (Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), Vec3::new(2.0, 0.0, 0.0), Vec3::new(3.0, 0.0, 0.0));
+        assert_close(catmull_rom(p0, p1, p2, p3, 0.25), Vec3::new(1.25, 0.0, 0.0));
+        assert_close(bezier(p0, p1, p2, p3, 0.25), Vec3::new(1.25, 0.0, 0.0));
+    }
+
+    #[test]
+    fn both_splines_hit_every_keyframe() {
+        for interpolation in [Interpolation::CatmullRom, Interpolation::Bezier] {
+            let path = path(interpolation);
+            for k in &path.keyframes {
+                assert_close(path.sample(k.time).unwrap().eye, k.eye);
+            }
+        }
+    }
+
+    #[test]
+    fn sampling_clamps_to_the_ends() {
+        let path = path(Interpolation::CatmullRom);
+        assert_close(path.sample(-1.0).unwrap().eye, path.keyframes[0].eye);
+        assert_close(path.sample(100.0).unwrap().eye, path.keyframes[3].eye);
+        assert!(CameraPath::new(Interpolation::Bezier).sample(1.0).is_none());
+    }
+
+    #[test]
+    fn keyframes_are_kept_in_time_order() {
+        let mut path = CameraPath::new(Interpolation::CatmullRom);
+        path.add_keyframe(keyframe(5.0, Vec3::new(1.0, 0.0, 0.0)));
+        path.add_keyframe(keyframe(1.0, Vec3::new(2.0, 0.0, 0.0)));
+        path.add_keyframe(keyframe(3.0, Vec3::new(3.0, 0.0, 0.0)));
+        let times: Vec<f32> = path.keyframes.iter().map(|k| k.time).collect();
+        assert_eq!(times, [1.0, 3.0, 5.0]);
+        assert_eq!(path.duration(), 5.0);
+    }
+
+    #[test]
+    fn saved_path_loads_back_the_same() {
+        let path = path(Interpolation::Bezier);
+        let filename = std::env::temp_dir().join(format!("camera_path_round_trip_{}.txt", std::process::id()));
+        let filename = filename.to_str().unwrap();
+        path.save(filename).unwrap();
+        let loaded = CameraPath::load(filename).unwrap();
+        fs::remove_file(filename).unwrap();
+
+        assert!(loaded.interpolation == Interpolation::Bezier);
+        assert_eq!(loaded.keyframes.len(), path.keyframes.len());
+        for (a, b) in loaded.keyframes.iter().zip(&path.keyframes) {
+            assert_eq!(a.time, b.time);
+            assert_close(a.eye, b.eye);
+            assert!((a.fov - b.fov).abs() < 1e-5);
+        }
+    }
+}
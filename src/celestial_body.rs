@@ -1,8 +1,20 @@
-use nalgebra_glm::{Mat4, Vec3};
+use nalgebra_glm::{Mat4, Vec3, Vec4};
 use crate::framebuffer::Framebuffer;
 use crate::{Uniforms, vertex::Vertex};
 use crate::shaders::{vertex_shader, fragment_shader_neptune, fragment_shader_jupiter, fragment_shader_venus, fragment_shader_mars, fragment_shader_earth, fragment_shader_mercury, fragment_shader_sun};
 
+/// Physical data shown in the info panel. It plays no part in rendering or the orbits.
+#[derive(Clone, Default)]
+pub struct BodyInfo {
+    pub radius_km: f32,
+    pub mass_kg: f64,
+    pub orbital_period_days: f32, // 0 for bodies that don't orbit
+    pub day_length_hours: f32,
+    pub mean_temperature_c: f32,
+    pub moons: u32,
+    pub description: String,
+}
+
 pub struct CelestialBody {
     pub name: String,
     pub vertex_array: Vec<Vertex>,
@@ -11,32 +23,86 @@ pub struct CelestialBody {
     pub rotation_speed: f32,
     pub orbit_speed: f32,
     pub scale: f32,
+    pub orbit_color: u32,
+    pub emission: f32, // Glow of the surface, 0 for bodies lit only by the sun
+    pub info: BodyInfo,
+}
+
+// Vertex between a and b, with every attribute interpolated in clip space
+fn lerp_vertex(a: &Vertex, b: &Vertex, t: f32) -> Vertex {
+    Vertex {
+        position: a.position.lerp(&b.position, t),
+        normal: a.normal.lerp(&b.normal, t).try_normalize(1.0e-6).unwrap_or(a.normal),
+        tex_coords: a.tex_coords.lerp(&b.tex_coords, t),
+        color: a.color.lerp(&b.color, t),
+        transformed_position: a.transformed_position,
+        transformed_normal: a.transformed_normal.lerp(&b.transformed_normal, t).try_normalize(1.0e-6).unwrap_or(a.transformed_normal),
+    }
+}
+
+/// Cuts a triangle against the near plane (z >= -w in clip space), so the part in front
+/// of the camera is still drawn when the camera gets close. Gives back 0 to 2 triangles.
+fn clip_near(triangle: [(Vec4, &Vertex); 3], viewport_matrix: &Mat4) -> Vec<[Vertex; 3]> {
+    let distance = |clip: &Vec4| clip.z + clip.w;
+
+    // Sutherland-Hodgman with a single plane
+    let mut polygon: Vec<(Vec4, Vertex)> = Vec::with_capacity(4);
+    for i in 0..3 {
+        let (clip_a, a) = triangle[i];
+        let (clip_b, b) = triangle[(i + 1) % 3];
+        let (distance_a, distance_b) = (distance(&clip_a), distance(&clip_b));
+
+        if distance_a >= 0.0 {
+            polygon.push((clip_a, a.clone()));
+        }
+        if (distance_a >= 0.0) != (distance_b >= 0.0) {
+            let t = distance_a / (distance_a - distance_b);
+            polygon.push((clip_a.lerp(&clip_b, t), lerp_vertex(a, b, t)));
+        }
+    }
+
+    // New vertices still need their screen position
+    for (clip, vertex) in polygon.iter_mut() {
+        let screen = viewport_matrix * Vec4::new(clip.x / clip.w, clip.y / clip.w, clip.z / clip.w, 1.0);
+        vertex.transformed_position = Vec3::new(screen.x, screen.y, screen.z);
+    }
+
+    (1..polygon.len().saturating_sub(1))
+        .map(|i| [polygon[0].1.clone(), polygon[i].1.clone(), polygon[i + 1].1.clone()])
+        .collect()
 }
 
 impl CelestialBody {
     pub fn get_model_matrix(&self, time: f32) -> Mat4 {
-        let orbit_angle = self.orbit_speed * time;
         let rotation_angle = self.rotation_speed * time;
 
-        let translation = Vec3::new(
-            self.orbit_radius * orbit_angle.cos(),
-            0.0,
-            self.orbit_radius * orbit_angle.sin(),
-        );
+        let translation = self.get_position(time);
 
         let rotation = Vec3::new(0.0, rotation_angle, 0.0);
 
         super::create_model_matrix(translation, self.scale, rotation)
     }
 
-    pub fn get_world_position(&self) -> Vec3 {
+    pub fn get_position(&self, time: f32) -> Vec3 {
+        let orbit_angle = self.orbit_speed * time;
+
         Vec3::new(
-            self.orbit_radius * (self.orbit_speed * self.rotation_speed).cos(),
+            self.orbit_radius * orbit_angle.cos(),
             0.0,
-            self.orbit_radius * (self.orbit_speed * self.rotation_speed).sin(),
+            self.orbit_radius * orbit_angle.sin(),
         )
     }
 
+    // Radius of the model after scaling, measured from its vertices
+    pub fn bounding_radius(&self) -> f32 {
+        let max_distance = self.vertex_array
+            .iter()
+            .map(|vertex| vertex.position.magnitude())
+            .fold(0.0, f32::max);
+
+        max_distance * self.scale
+    }
+
     pub fn render(&self, framebuffer: &mut Framebuffer, uniforms: &Uniforms) {
         let mut transformed_vertices = Vec::with_capacity(self.vertex_array.len());
         for vertex in &self.vertex_array {
@@ -44,23 +110,49 @@ impl CelestialBody {
             transformed_vertices.push(transformed);
         }
 
+        // Clip space positions, to tell which triangles reach behind the near plane
+        let model_view_projection = uniforms.projection_matrix * uniforms.view_matrix * uniforms.model_matrix;
+        let clip_positions: Vec<Vec4> = self.vertex_array
+            .iter()
+            .map(|vertex| model_view_projection * Vec4::new(vertex.position.x, vertex.position.y, vertex.position.z, 1.0))
+            .collect();
+
         let mut triangles = Vec::new();
         for i in (0..transformed_vertices.len()).step_by(3) {
             if i + 2 < transformed_vertices.len() {
-                triangles.push([
-                    transformed_vertices[i].clone(),
-                    transformed_vertices[i + 1].clone(),
-                    transformed_vertices[i + 2].clone(),
-                ]);
+                let corners = [i, i + 1, i + 2];
+                let behind = corners.iter().filter(|&&index| clip_positions[index].z < -clip_positions[index].w).count();
+
+                match behind {
+                    0 => triangles.push(corners.map(|index| transformed_vertices[index].clone())),
+                    3 => {}
+                    _ => triangles.extend(clip_near(corners.map(|index| (clip_positions[index], &transformed_vertices[index])), &uniforms.viewport_matrix)),
+                }
             }
         }
 
+        // Triangles entirely off screen or past the far plane would only waste time
+        let (width, height) = (framebuffer.width as f32, framebuffer.height as f32);
+        triangles.retain(|tri| {
+            let positions = tri.iter().map(|v| v.transformed_position);
+            let past_far = positions.clone().all(|p| p.z > 1.0);
+            let off_screen = positions.clone().all(|p| p.x < 0.0) || positions.clone().all(|p| p.x > width) ||
+                             positions.clone().all(|p| p.y < 0.0) || positions.clone().all(|p| p.y > height);
+            !past_far && !off_screen
+        });
+
+        let samples = framebuffer.sample_offsets().to_vec();
+        let per_sample = framebuffer.antialiasing().shades_per_sample();
         let mut fragments = Vec::new();
         for tri in &triangles {
-            fragments.extend(crate::triangle(&tri[0], &tri[1], &tri[2]));
+            fragments.extend(crate::triangle(&tri[0], &tri[1], &tri[2], &samples, per_sample, framebuffer.width, framebuffer.height));
         }
 
         for fragment in fragments {
+            // Negative positions would saturate to 0 and smear along the top or left edge
+            if fragment.position.x < 0.0 || fragment.position.y < 0.0 {
+                continue;
+            }
             let x = fragment.position.x as usize;
             let y = fragment.position.y as usize;
             if x < framebuffer.width && y < framebuffer.height {
@@ -74,10 +166,26 @@ impl CelestialBody {
                     _ => fragment_shader_neptune(&fragment, uniforms),
                 };
 
-                let color = shaded_color.to_hex();
-                framebuffer.set_current_color(color);
-                framebuffer.point(x, y, fragment.depth);
+                framebuffer.set_current_light(&shaded_color, self.emission);
+                framebuffer.point_samples(x, y, fragment.position, fragment.depth, fragment.depth_slope, fragment.coverage);
             }
         }
     }
 }
+
+// A body resting at the origin with the given mesh, for tests that only need something to look at
+#[cfg(test)]
+pub fn still_body(name: &str, vertex_array: Vec<Vertex>) -> CelestialBody {
+    CelestialBody {
+        name: name.to_string(),
+        vertex_array,
+        shader_type: 0,
+        orbit_radius: 0.0,
+        rotation_speed: 0.0,
+        orbit_speed: 0.0,
+        scale: 1.0,
+        orbit_color: 0xFFFFFF,
+        emission: 0.0,
+        info: BodyInfo::default(),
+    }
+}
@@ -0,0 +1,266 @@
+use crate::antialiasing::Antialiasing;
+use crate::bloom::Bloom;
+use crate::display::{self, Filter};
+use crate::tonemap::{self, Operator, ToneMapping};
+use crate::{hud, poster, scene};
+
+pub const USAGE: &str = "\
+Usage: Proy3 [options]
+
+Window:
+  --size WxH             Window size in pixels (default 600x600)
+  --scale F              Framebuffer resolution relative to the window, 0.25 to 2 (default 1)
+  --filter NAME          Scaling of the framebuffer to the window: nearest or bilinear
+                         (default bilinear)
+  --aa MODE              Antialiasing: off, msaa2, msaa4, msaa8, or ssaa2 to ssaa4 with an
+                         optional -lanczos (e.g. ssaa2-lanczos) instead of a box downsample
+                         (default off)
+  --tonemap NAME         Tone mapping: clamp, reinhard or aces (default clamp)
+  --exposure EV          Exposure in stops from -8 to 8, negative darkens (default 0)
+  --bloom-intensity F    Strength of the glow around bright and emissive bodies (default 0.5)
+  --no-bloom             No glow
+  --fps N                Frame rate of the window, recordings and headless runs (default 60)
+
+Scene:
+  --scene FILE           Bodies to load (default ./assets/scene.txt)
+  --start-date DATE      Simulation date to start at, as YYYY-MM-DD (default 2000-01-01,
+                         or the time saved in --bookmark)
+  --time-scale F         Simulation speed multiplier from 0 (paused) to 1000 (default 1)
+  --bookmark NAME        Start at a camera bookmark from bookmarks.txt
+  --bookmark-name NAME   Name for bookmarks saved with M (then 'NAME 2', ...; default
+                         'bookmark N')
+  --seed N               Seed of the random starfield (default 42)
+  --sky FILE             Equirectangular sky image instead of the starfield
+  --skybox STEM          Cubemap sky from STEM_px.png, STEM_nx.png, ...
+  --no-stars             Plain background color
+
+Output without a window:
+  --headless OUT         Record to a PNG directory, or a .y4m or .gif file
+  --frames N             Frames to record (default: the length of --path)
+  --path FILE            Camera path to play; also used for the first view of exports
+  --panorama FILE        Export a 360 panorama and cube faces
+  --poster FILE          Export a high-resolution poster
+  --poster-size WxH      Poster size (default 7680x4320)
+
+  --help                 Show this message
+";
+
+/// Everything that can be set from the command line.
+pub struct Args {
+    pub window_width: usize,
+    pub window_height: usize,
+    pub scale: f32,
+    pub filter: Filter,
+    pub antialiasing: Antialiasing,
+    pub tone_mapping: ToneMapping,
+    pub bloom: Bloom,
+    pub fps: u32,
+    pub scene_file: String,
+    pub start_day: Option<i64>, // Days after 2000-01-01
+    pub time_scale: f32,
+    pub bookmark: Option<String>,
+    pub bookmark_name: Option<String>,
+    pub seed: u64,
+    pub sky_file: Option<String>,
+    pub skybox_stem: Option<String>,
+    pub no_stars: bool,
+    pub headless: Option<String>,
+    pub frames: Option<u32>,
+    pub path_file: Option<String>,
+    pub panorama_file: Option<String>,
+    pub poster_file: Option<String>,
+    pub poster_size: (usize, usize),
+    pub help: bool,
+}
+
+// Past this the simulation would skip most of an orbit every frame
+const MAX_TIME_SCALE: f32 = 1000.0;
+
+const VALUE_FLAGS: &[&str] = &[
+    "--size", "--scale", "--filter", "--aa", "--tonemap", "--exposure", "--bloom-intensity", "--fps",
+    "--scene", "--start-date", "--time-scale", "--bookmark", "--bookmark-name", "--seed", "--sky", "--skybox", "--headless", "--frames", "--path", "--panorama", "--poster", "--poster-size",
+];
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value.parse::<T>().map_err(|_| format!("invalid value '{}' for {}", value, flag))
+}
+
+// Like parse_number, but "NaN" and "inf" parse as f32 and would slip past range checks
+fn parse_float(flag: &str, value: &str) -> Result<f32, String> {
+    let number: f32 = parse_number(flag, value)?;
+    if number.is_finite() {
+        Ok(number)
+    } else {
+        Err(format!("invalid value '{}' for {}, expected a finite number", value, flag))
+    }
+}
+
+impl Args {
+    fn new() -> Self {
+        Args {
+            window_width: 600,
+            window_height: 600,
+            scale: 1.0,
+            filter: Filter::Bilinear,
+            antialiasing: Antialiasing::Off,
+            tone_mapping: ToneMapping::new(),
+            bloom: Bloom::new(),
+            fps: 60,
+            scene_file: scene::DEFAULT_SCENE_FILE.to_string(),
+            start_day: None,
+            time_scale: 1.0,
+            bookmark: None,
+            bookmark_name: None,
+            seed: 42,
+            sky_file: None,
+            skybox_stem: None,
+            no_stars: false,
+            headless: None,
+            frames: None,
+            path_file: None,
+            panorama_file: None,
+            poster_file: None,
+            poster_size: poster::DEFAULT_SIZE,
+            help: false,
+        }
+    }
+
+    /// Parses the arguments after the program name.
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut parsed = Args::new();
+        let mut args = args.iter();
+
+        while let Some(flag) = args.next() {
+            // Flags without a value
+            match flag.as_str() {
+                "--help" | "-h" => {
+                    parsed.help = true;
+                    continue;
+                }
+                "--no-stars" => {
+                    parsed.no_stars = true;
+                    continue;
+                }
+                "--no-bloom" => {
+                    parsed.bloom.enabled = false;
+                    continue;
+                }
+                _ => {}
+            }
+
+            if !VALUE_FLAGS.contains(&flag.as_str()) {
+                return Err(format!("unknown option {}", flag));
+            }
+            let value = args.next().ok_or_else(|| format!("missing value for {}", flag))?;
+            match flag.as_str() {
+                "--size" => {
+                    let (width, height) = poster::parse_size(value).ok_or_else(|| format!("invalid size '{}', expected WxH", value))?;
+                    parsed.window_width = width;
+                    parsed.window_height = height;
+                }
+                "--scale" => parsed.scale = parse_float(flag, value)?,
+                "--filter" => {
+                    parsed.filter = Filter::parse(value).ok_or_else(|| format!("invalid filter '{}', expected nearest or bilinear", value))?
+                }
+                "--aa" => parsed.antialiasing = Antialiasing::parse(value).ok_or_else(|| format!("invalid antialiasing mode '{}'", value))?,
+                "--tonemap" => {
+                    parsed.tone_mapping.operator = Operator::parse(value).ok_or_else(|| format!("invalid tone mapping '{}', expected clamp, reinhard or aces", value))?
+                }
+                "--exposure" => parsed.tone_mapping.exposure = parse_float(flag, value)?,
+                "--bloom-intensity" => parsed.bloom.intensity = parse_float(flag, value)?,
+                "--fps" => parsed.fps = parse_number(flag, value)?,
+                "--scene" => parsed.scene_file = value.clone(),
+                "--start-date" => {
+                    let day = hud::parse_date(value).ok_or_else(|| format!("invalid date '{}', expected YYYY-MM-DD", value))?;
+                    if day < 0 {
+                        return Err(String::from("--start-date can't be before 2000-01-01"));
+                    }
+                    parsed.start_day = Some(day);
+                }
+                "--time-scale" => parsed.time_scale = parse_float(flag, value)?,
+                "--bookmark" => parsed.bookmark = Some(value.clone()),
+                "--bookmark-name" => {
+                    // Names are the rest of a line in bookmarks.txt, which is trimmed on loading
+                    let name = value.trim();
+                    if name.is_empty() || name.contains(['\n', '\r']) {
+                        return Err(format!("invalid bookmark name '{}'", value));
+                    }
+                    parsed.bookmark_name = Some(name.to_string());
+                }
+                "--seed" => parsed.seed = parse_number(flag, value)?,
+                "--sky" => parsed.sky_file = Some(value.clone()),
+                "--skybox" => parsed.skybox_stem = Some(value.clone()),
+                "--headless" => parsed.headless = Some(value.clone()),
+                "--frames" => parsed.frames = Some(parse_number(flag, value)?),
+                "--path" => parsed.path_file = Some(value.clone()),
+                "--panorama" => parsed.panorama_file = Some(value.clone()),
+                "--poster" => parsed.poster_file = Some(value.clone()),
+                "--poster-size" => {
+                    parsed.poster_size = poster::parse_size(value).ok_or_else(|| format!("invalid size '{}', expected WxH", value))?
+                }
+                _ => unreachable!(),
+            }
+        }
+
+        if parsed.fps == 0 {
+            return Err(String::from("--fps must be at least 1"));
+        }
+        if !(display::MIN_SCALE..=display::MAX_SCALE).contains(&parsed.scale) {
+            return Err(format!("--scale must be between {} and {}", display::MIN_SCALE, display::MAX_SCALE));
+        }
+        if !(0.0..=MAX_TIME_SCALE).contains(&parsed.time_scale) {
+            return Err(format!("--time-scale must be between 0 and {}", MAX_TIME_SCALE));
+        }
+        if parsed.tone_mapping.exposure.abs() > tonemap::MAX_EXPOSURE {
+            return Err(format!("--exposure must be between {} and {}", -tonemap::MAX_EXPOSURE, tonemap::MAX_EXPOSURE));
+        }
+        if parsed.bloom.intensity < 0.0 {
+            return Err(String::from("--bloom-intensity can't be negative"));
+        }
+        Ok(parsed)
+    }
+
+    pub fn framebuffer_size(&self) -> (usize, usize) {
+        display::scaled_size(self.window_width, self.window_height, self.scale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(flags: &[&str]) -> Result<Args, String> {
+        Args::parse(&flags.iter().map(|flag| flag.to_string()).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn rejects_numbers_that_are_not_finite() {
+        for flag in ["--time-scale", "--exposure", "--bloom-intensity", "--scale"] {
+            for value in ["NaN", "inf", "-inf"] {
+                assert!(parse(&[flag, value]).is_err(), "{} {} was accepted", flag, value);
+            }
+        }
+    }
+
+    #[test]
+    fn accepts_finite_numbers() {
+        let args = parse(&["--time-scale", "2.5", "--exposure", "0.5", "--bloom-intensity", "0"]).unwrap();
+        assert_eq!(args.time_scale, 2.5);
+        assert_eq!(args.tone_mapping.exposure, 0.5);
+        assert_eq!(args.bloom.intensity, 0.0);
+    }
+
+    #[test]
+    fn rejects_time_scale_past_the_limit() {
+        assert!(parse(&["--time-scale", "1e9"]).is_err());
+        assert!(parse(&["--time-scale", "-1"]).is_err());
+        assert_eq!(parse(&["--time-scale", "1000"]).unwrap().time_scale, 1000.0);
+    }
+
+    #[test]
+    fn rejects_exposure_past_the_limit() {
+        assert!(parse(&["--exposure", "200"]).is_err());
+        assert!(parse(&["--exposure", "-8.5"]).is_err());
+        assert_eq!(parse(&["--exposure", "-8"]).unwrap().tone_mapping.exposure, -8.0);
+    }
+}
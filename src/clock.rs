@@ -0,0 +1,96 @@
+use super::SIMULATION_STEP;
+
+const MAX_FRAME_TIME: f32 = 0.25;
+
+// Seconds a frame stands for. Capped so a long stall doesn't make the simulation race to
+// catch up. While recording, every frame is one video frame apart, however long it took.
+pub fn frame_seconds(elapsed: f32, recording_fps: Option<u32>) -> f32 {
+    match recording_fps {
+        Some(fps) => 1.0 / fps as f32,
+        None => elapsed.min(MAX_FRAME_TIME),
+    }
+}
+
+/// Simulation time in whole ticks, plus the real time that has built up towards the next one.
+pub struct SimulationClock {
+    pub tick: u32,
+    accumulator: f32, // Seconds of simulation time not yet turned into a tick
+}
+
+impl SimulationClock {
+    pub fn new(tick: u32) -> Self {
+        SimulationClock { tick, accumulator: 0.0 }
+    }
+
+    // Advances by `seconds` of simulation time, in as many whole ticks as fit
+    pub fn advance(&mut self, seconds: f32) {
+        self.accumulator += seconds;
+        let ticks = (self.accumulator / SIMULATION_STEP).floor();
+        self.accumulator -= ticks * SIMULATION_STEP;
+        self.tick = self.tick.saturating_add(ticks as u32);
+    }
+
+    // Time to draw at, between this tick and the next. Body positions are worked out
+    // from the time alone, so there is no earlier state to blend with.
+    pub fn render_time(&self) -> f32 {
+        self.tick as f32 + self.accumulator / SIMULATION_STEP
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn whole_ticks_advance_and_the_rest_carries_over() {
+        let mut clock = SimulationClock::new(10);
+        clock.advance(2.5 * SIMULATION_STEP);
+        assert_eq!(clock.tick, 12);
+        clock.advance(0.75 * SIMULATION_STEP);
+        assert_eq!(clock.tick, 13);
+        // A paused clock stays put
+        clock.advance(0.0);
+        assert_eq!(clock.tick, 13);
+    }
+
+    #[test]
+    fn rendering_starts_at_the_start_tick_and_moves_smoothly() {
+        let mut clock = SimulationClock::new(100);
+        assert_eq!(clock.render_time(), 100.0);
+
+        let mut previous = clock.render_time();
+        for _ in 0..50 {
+            clock.advance(0.3 * SIMULATION_STEP);
+            let time = clock.render_time();
+            assert!(time > previous && time - previous < 0.31, "{} -> {}", previous, time);
+            assert!(time >= clock.tick as f32 && time < clock.tick as f32 + 1.0);
+            previous = time;
+        }
+        assert!((previous - 115.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn frames_last_their_real_time_unless_recording_or_stalled() {
+        assert_eq!(frame_seconds(0.016, None), 0.016);
+        assert_eq!(frame_seconds(3.0, None), MAX_FRAME_TIME);
+        assert_eq!(frame_seconds(0.5, Some(30)), 1.0 / 30.0);
+
+        // The same second of simulation, whether it comes in a few slow frames or many fast ones
+        let (mut slow, mut fast) = (SimulationClock::new(0), SimulationClock::new(0));
+        for _ in 0..5 {
+            slow.advance(frame_seconds(0.2, None));
+        }
+        for _ in 0..125 {
+            fast.advance(frame_seconds(0.008, None));
+        }
+        assert!((slow.render_time() - fast.render_time()).abs() < 0.01);
+        assert!((slow.render_time() - 1.0 / SIMULATION_STEP).abs() < 0.01);
+    }
+
+    #[test]
+    fn many_ticks_at_once_saturate_instead_of_overflowing() {
+        let mut clock = SimulationClock::new(u32::MAX - 5);
+        clock.advance(1.0e6);
+        assert_eq!(clock.tick, u32::MAX);
+    }
+}
@@ -18,7 +18,9 @@ impl Color {
     }
 
     pub fn to_hex(&self) -> u32 {
-        ((self.r as u32) << 16) | ((self.g as u32) << 8) | (self.b as u32)
+        // Clamped first, so overbright channels saturate instead of spilling into the next one
+        let channel = |value: f32| value.round().clamp(0.0, 255.0) as u32;
+        (channel(self.r) << 16) | (channel(self.g) << 8) | channel(self.b)
     }
 
     pub fn lerp(&self, other: &Color, t: f32) -> Self {
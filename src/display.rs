@@ -0,0 +1,197 @@
+use crate::framebuffer::Framebuffer;
+
+pub const MIN_SCALE: f32 = 0.25;
+pub const MAX_SCALE: f32 = 2.0;
+// Internal resolutions the scale keys step through
+const SCALE_STEPS: [f32; 7] = [0.25, 0.5, 0.75, 1.0, 1.25, 1.5, 2.0];
+
+/// How the framebuffer is resampled to the window size.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Filter {
+    Nearest,
+    Bilinear,
+}
+
+impl Filter {
+    pub fn parse(name: &str) -> Option<Filter> {
+        match name {
+            "nearest" => Some(Filter::Nearest),
+            "bilinear" => Some(Filter::Bilinear),
+            _ => None,
+        }
+    }
+
+    pub fn next(&self) -> Filter {
+        match self {
+            Filter::Nearest => Filter::Bilinear,
+            Filter::Bilinear => Filter::Nearest,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Filter::Nearest => "nearest",
+            Filter::Bilinear => "bilinear",
+        }
+    }
+}
+
+/// The window, which the framebuffer is scaled up (or down) to fit.
+pub struct Display {
+    pub window_width: usize,
+    pub window_height: usize,
+    pub scale: f32,
+    pub filter: Filter,
+    buffer: Vec<u32>,
+}
+
+/// Internal resolution for a window of the given size, at least one pixel each way.
+pub fn scaled_size(window_width: usize, window_height: usize, scale: f32) -> (usize, usize) {
+    (
+        ((window_width as f32 * scale).round() as usize).max(1),
+        ((window_height as f32 * scale).round() as usize).max(1),
+    )
+}
+
+fn lerp_color(a: u32, b: u32, t: f32) -> [f32; 3] {
+    let channel = |shift: u32| {
+        let a = ((a >> shift) & 0xFF) as f32;
+        let b = ((b >> shift) & 0xFF) as f32;
+        a + (b - a) * t
+    };
+    [channel(16), channel(8), channel(0)]
+}
+
+impl Display {
+    pub fn new(window_width: usize, window_height: usize, scale: f32, filter: Filter) -> Self {
+        Display {
+            window_width,
+            window_height,
+            scale: scale.clamp(MIN_SCALE, MAX_SCALE),
+            filter,
+            buffer: vec![0; window_width * window_height],
+        }
+    }
+
+    pub fn framebuffer_size(&self) -> (usize, usize) {
+        scaled_size(self.window_width, self.window_height, self.scale)
+    }
+
+    pub fn resize(&mut self, window_width: usize, window_height: usize) {
+        self.window_width = window_width.max(1);
+        self.window_height = window_height.max(1);
+        self.buffer = vec![0; self.window_width * self.window_height];
+    }
+
+    // Moves to the next larger (or smaller) step of the internal resolution
+    pub fn step_scale(&mut self, larger: bool) {
+        let current = self.scale;
+        let next = if larger {
+            SCALE_STEPS.iter().copied().find(|&step| step > current + 1e-3)
+        } else {
+            SCALE_STEPS.iter().rev().copied().find(|&step| step < current - 1e-3)
+        };
+        if let Some(step) = next {
+            self.scale = step;
+        }
+    }
+
+    // Window coordinates to framebuffer coordinates, for picking
+    pub fn to_framebuffer(&self, framebuffer: &Framebuffer, (x, y): (f32, f32)) -> (f32, f32) {
+        (
+            x * framebuffer.width as f32 / self.window_width as f32,
+            y * framebuffer.height as f32 / self.window_height as f32,
+        )
+    }
+
+    /// Resamples the framebuffer to the window size and returns the pixels to show.
+    pub fn present(&mut self, framebuffer: &Framebuffer) -> &[u32] {
+        let (source_width, source_height) = (framebuffer.width, framebuffer.height);
+        if source_width == self.window_width && source_height == self.window_height {
+            self.buffer.copy_from_slice(&framebuffer.buffer);
+            return &self.buffer;
+        }
+
+        let step_x = source_width as f32 / self.window_width as f32;
+        let step_y = source_height as f32 / self.window_height as f32;
+
+        for y in 0..self.window_height {
+            let row = &mut self.buffer[y * self.window_width..(y + 1) * self.window_width];
+            match self.filter {
+                Filter::Nearest => {
+                    let source_y = ((y as f32 + 0.5) * step_y) as usize;
+                    let source_row = source_y.min(source_height - 1) * source_width;
+                    for (x, pixel) in row.iter_mut().enumerate() {
+                        let source_x = (((x as f32 + 0.5) * step_x) as usize).min(source_width - 1);
+                        *pixel = framebuffer.buffer[source_row + source_x];
+                    }
+                }
+                Filter::Bilinear => {
+                    // Sample between the four nearest pixel centers
+                    let source_y = ((y as f32 + 0.5) * step_y - 0.5).clamp(0.0, (source_height - 1) as f32);
+                    let y0 = source_y as usize;
+                    let y1 = (y0 + 1).min(source_height - 1);
+                    let ty = source_y - y0 as f32;
+
+                    for (x, pixel) in row.iter_mut().enumerate() {
+                        let source_x = ((x as f32 + 0.5) * step_x - 0.5).clamp(0.0, (source_width - 1) as f32);
+                        let x0 = source_x as usize;
+                        let x1 = (x0 + 1).min(source_width - 1);
+                        let tx = source_x - x0 as f32;
+
+                        let top = lerp_color(framebuffer.buffer[y0 * source_width + x0], framebuffer.buffer[y0 * source_width + x1], tx);
+                        let bottom = lerp_color(framebuffer.buffer[y1 * source_width + x0], framebuffer.buffer[y1 * source_width + x1], tx);
+                        let channel = |index: usize| ((top[index] + (bottom[index] - top[index]) * ty).round() as u32).min(255);
+                        *pixel = (channel(0) << 16) | (channel(1) << 8) | channel(2);
+                    }
+                }
+            }
+        }
+
+        &self.buffer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scale_steps_stay_in_range() {
+        let mut display = Display::new(800, 600, 1.0, Filter::Nearest);
+        for _ in 0..10 {
+            display.step_scale(true);
+        }
+        assert_eq!(display.scale, MAX_SCALE);
+        for _ in 0..10 {
+            display.step_scale(false);
+            assert!((MIN_SCALE..=MAX_SCALE).contains(&display.scale));
+        }
+        assert_eq!(display.scale, MIN_SCALE);
+
+        // A scale between steps moves to the neighboring step
+        let mut display = Display::new(800, 600, 0.6, Filter::Nearest);
+        display.step_scale(true);
+        assert_eq!(display.scale, 0.75);
+        assert_eq!(Display::new(800, 600, 9.0, Filter::Nearest).scale, MAX_SCALE);
+    }
+
+    #[test]
+    fn present_fills_the_window() {
+        for (scale, filter) in [(0.25, Filter::Nearest), (0.25, Filter::Bilinear), (2.0, Filter::Nearest), (2.0, Filter::Bilinear), (0.6, Filter::Bilinear)] {
+            let mut display = Display::new(101, 67, scale, filter);
+            let (width, height) = display.framebuffer_size();
+            let mut framebuffer = Framebuffer::new(width, height);
+            framebuffer.buffer.fill(0x336699);
+
+            let shown = display.present(&framebuffer);
+            assert_eq!(shown.len(), 101 * 67, "scale {}", scale);
+            // A flat image stays flat whatever the filter
+            assert!(shown.iter().all(|&pixel| pixel == 0x336699), "scale {}", scale);
+
+            // The framebuffer is only remade on the next frame after a resize
+            display.resize(50, 40);
+            assert_eq!(display.present(&framebuffer).len(), 50 * 40);
+        }
+    }
+}
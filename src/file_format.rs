@@ -0,0 +1,6 @@
+use std::io;
+
+/// Error for a file (camera path, bookmarks, scene, sky) that could be read but not understood.
+pub fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
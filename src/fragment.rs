@@ -10,9 +10,12 @@ pub struct Fragment {
     pub normal: Vec3,
     pub intensity: f32,
     pub vertex_position: Vec3,
+    pub coverage: u32, // Bit per sample of the pixel the triangle covers
+    pub depth_slope: Vec2, // Change in depth per pixel in x and y
 }
 
 impl Fragment {
+    // Covers the first sample only, with flat depth; the rasterizer sets both afterwards
     pub fn new(position: Vec2, color: Color, depth: f32, normal: Vec3, intensity: f32, vertex_position: Vec3) -> Self {
         Fragment {
             position,
@@ -21,6 +24,8 @@ impl Fragment {
             normal,
             intensity,
             vertex_position,
+            coverage: 1,
+            depth_slope: Vec2::zeros(),
         }
     }
 }
\ No newline at end of file
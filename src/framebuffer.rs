@@ -1,10 +1,43 @@
+use nalgebra_glm::{Vec2, Vec3};
+use crate::antialiasing::{self, Antialiasing, Downsample};
+use crate::bloom::Bloom;
+use crate::color::Color;
+use crate::tonemap::{self, ToneMapping};
+use crate::viewport::Viewport;
+
+// Mixes two 0xRRGGBB colors, `alpha` of the way from `below` to `above`
+fn mix_hex(below: u32, above: u32, alpha: f32) -> u32 {
+	let channel = |shift: u32| {
+		let below = ((below >> shift) & 0xFF) as f32;
+		let above = ((above >> shift) & 0xFF) as f32;
+		((below + (above - below) * alpha).round() as u32) << shift
+	};
+	channel(16) | channel(8) | channel(0)
+}
+
+#[derive(Clone)]
 pub struct Framebuffer {
 	pub width: usize,
 	pub height: usize,
-	pub buffer: Vec<u32>,
+	pub buffer: Vec<u32>, // What is shown, filled in by `resolve`
 	pub zbuffer: Vec<f32>,
+	pub id_buffer: Option<Vec<Option<usize>>>,
 	background_color: u32,
 	current_color: u32,
+	current_light: [f32; 3], // The current color as linear light
+	current_emission: [f32; 3],
+	current_id: Option<usize>,
+	scissor: Option<Viewport>,
+	antialiasing: Antialiasing,
+	sample_offsets: Vec<Vec2>,
+	// Linear HDR color, emitted light and depth of every sample, pixel by pixel
+	sample_colors: Vec<[f32; 3]>,
+	sample_emission: Vec<[f32; 3]>,
+	sample_depths: Vec<f32>,
+	// After `resolve`, drawing goes straight to `buffer`, like the HUD does
+	resolved: bool,
+	// Viewports cleared since the last resolve, bottom to top. Bloom stays inside each one.
+	viewports: Vec<Viewport>,
 }
 
 impl Framebuffer {
@@ -14,8 +47,107 @@ impl Framebuffer {
 			height,
 			buffer: vec![0; width * height],
 			zbuffer: vec![f32::INFINITY; width * height],
+			id_buffer: None,
 			background_color: 0x000000,
 			current_color: 0xFFFFFF,
+			current_light: [1.0; 3],
+			current_emission: [0.0; 3],
+			current_id: None,
+			scissor: None,
+			antialiasing: Antialiasing::Off,
+			sample_offsets: Antialiasing::Off.sample_offsets(),
+			sample_colors: vec![[0.0; 3]; width * height],
+			sample_emission: vec![[0.0; 3]; width * height],
+			sample_depths: vec![f32::INFINITY; width * height],
+			resolved: false,
+			viewports: Vec::new(),
+		}
+	}
+
+	pub fn set_antialiasing(&mut self, antialiasing: Antialiasing) {
+		self.antialiasing = antialiasing;
+		self.sample_offsets = antialiasing.sample_offsets();
+		let count = self.width * self.height * self.sample_offsets.len();
+		self.sample_colors = vec![tonemap::hex_to_linear(self.background_color); count];
+		self.sample_emission = vec![[0.0; 3]; count];
+		self.sample_depths = vec![f32::INFINITY; count];
+	}
+
+	pub fn antialiasing(&self) -> Antialiasing {
+		self.antialiasing
+	}
+
+	pub fn sample_offsets(&self) -> &[Vec2] {
+		&self.sample_offsets
+	}
+
+	fn samples_of(&self, index: usize) -> std::ops::Range<usize> {
+		let count = self.sample_offsets.len();
+		index * count..(index + 1) * count
+	}
+
+	/// Averages the samples of every pixel, adds the bloom and tone maps them into `buffer`.
+	/// Anything drawn afterwards, until the next clear, is written to `buffer` as it is.
+	pub fn resolve(&mut self, tone_mapping: &ToneMapping, bloom: &Bloom) {
+		let count = self.sample_offsets.len();
+		let mut light = match self.antialiasing {
+			Antialiasing::Ssaa(factor, Downsample::Lanczos) => {
+				let (width, colors) = (self.width, &self.sample_colors);
+				// The samples of a pixel form a factor x factor grid, so they read as a larger image
+				antialiasing::lanczos_downsample(self.width, self.height, factor, |x, y| {
+					let pixel = (y / factor) * width + x / factor;
+					colors[pixel * count + (y % factor) * factor + x % factor]
+				})
+			}
+			_ => self.sample_colors.chunks(count).map(antialiasing::box_resolve).collect(),
+		};
+		let emission: Vec<[f32; 3]> = self.sample_emission.chunks(count).map(antialiasing::box_resolve).collect();
+		if self.viewports.is_empty() {
+			bloom.apply(&mut light, &emission, self.width, self.height);
+		} else {
+			self.bloom_viewports(bloom, &mut light, &emission);
+		}
+
+		for (index, pixel) in light.into_iter().enumerate() {
+			self.buffer[index] = tone_mapping.map(pixel);
+			self.zbuffer[index] = self.sample_depths[self.samples_of(index)].iter().copied().fold(f32::INFINITY, f32::min);
+		}
+		self.resolved = true;
+		self.viewports.clear();
+	}
+
+	// Blooms every viewport on its own, so glow doesn't cross into the views beside or over it.
+	// A pixel belongs to the topmost viewport over it and only glows with the light of that one.
+	fn bloom_viewports(&self, bloom: &Bloom, light: &mut [[f32; 3]], emission: &[[f32; 3]]) {
+		let mut owners = vec![None; self.width * self.height];
+		for (index, viewport) in self.viewports.iter().enumerate() {
+			for y in viewport.y..(viewport.y + viewport.height).min(self.height) {
+				for x in viewport.x..(viewport.x + viewport.width).min(self.width) {
+					owners[y * self.width + x] = Some(index);
+				}
+			}
+		}
+
+		for (index, viewport) in self.viewports.iter().enumerate() {
+			let width = viewport.width.min(self.width.saturating_sub(viewport.x));
+			let height = viewport.height.min(self.height.saturating_sub(viewport.y));
+			if width == 0 || height == 0 {
+				continue;
+			}
+			let pixels = || (0..height).flat_map(move |y| (0..width).map(move |x| (viewport.y + y) * self.width + viewport.x + x));
+			let owned = |pixel: usize| owners[pixel] == Some(index);
+			let own_light: Vec<[f32; 3]> = pixels().map(|pixel| if owned(pixel) { light[pixel] } else { [0.0; 3] }).collect();
+			let own_emission: Vec<[f32; 3]> = pixels().map(|pixel| if owned(pixel) { emission[pixel] } else { [0.0; 3] }).collect();
+
+			if let Some(glow) = bloom.glow(&own_light, &own_emission, width, height) {
+				for (pixel, added) in pixels().zip(glow) {
+					if owned(pixel) {
+						for (channel, added) in light[pixel].iter_mut().zip(added) {
+							*channel += added;
+						}
+					}
+				}
+			}
 		}
 	}
 
@@ -26,16 +158,258 @@ impl Framebuffer {
 		for depth in self.zbuffer.iter_mut(){
 			*depth = f32::INFINITY;
 		}
+		if let Some(ids) = self.id_buffer.as_mut() {
+			for id in ids.iter_mut() {
+				*id = None;
+			}
+		}
+		self.sample_colors.fill(tonemap::hex_to_linear(self.background_color));
+		self.sample_emission.fill([0.0; 3]);
+		self.sample_depths.fill(f32::INFINITY);
+		self.resolved = false;
+		self.viewports.clear();
 	}
 
-	pub fn point(&mut self, x: usize, y: usize, depth: f32) {
+	// Clears color, depth and IDs inside the viewport only
+	pub fn clear_viewport(&mut self, viewport: &Viewport) {
+		let clear_light = tonemap::hex_to_linear(viewport.clear_color);
+		let x_end = (viewport.x + viewport.width).min(self.width);
+		let y_end = (viewport.y + viewport.height).min(self.height);
+		for y in viewport.y..y_end {
+			for x in viewport.x..x_end {
+				let index = y * self.width + x;
+				self.buffer[index] = viewport.clear_color;
+				self.zbuffer[index] = f32::INFINITY;
+				if let Some(ids) = self.id_buffer.as_mut() {
+					ids[index] = None;
+				}
+				for sample in self.samples_of(index) {
+					self.sample_colors[sample] = clear_light;
+					self.sample_emission[sample] = [0.0; 3];
+					self.sample_depths[sample] = f32::INFINITY;
+				}
+			}
+		}
+		self.resolved = false;
+		// Cleared again, as the second eye of an anaglyph is, it moves to the top
+		let same_rect = |other: &Viewport| (other.x, other.y, other.width, other.height) == (viewport.x, viewport.y, viewport.width, viewport.height);
+		self.viewports.retain(|other| !same_rect(other));
+		self.viewports.push(*viewport);
+	}
+
+	// Points outside the scissor viewport are discarded
+	pub fn set_scissor(&mut self, viewport: Option<Viewport>) {
+		self.scissor = viewport;
+	}
+
+	// Index of the pixel, if it is inside the framebuffer and the scissor
+	fn index_of(&self, x: usize, y: usize) -> Option<usize> {
+		if let Some(scissor) = &self.scissor {
+			if x < scissor.x || x >= scissor.x + scissor.width || y < scissor.y || y >= scissor.y + scissor.height {
+				return None;
+			}
+		}
 		if x < self.width && y < self.height {
-			let index = y *self.width+x;
+			Some(y * self.width + x)
+		} else {
+			None
+		}
+	}
+
+	pub fn point(&mut self, x: usize, y: usize, depth: f32) {
+		if !self.resolved {
+			let all = (1u32 << self.sample_offsets.len()).wrapping_sub(1);
+			self.point_samples(x, y, Vec2::new(x as f32, y as f32), depth, Vec2::zeros(), all);
+			return;
+		}
+		if let Some(index) = self.index_of(x, y) {
 			if self.zbuffer[index] > depth{
 				self.buffer[index] = self.current_color;
 				self.zbuffer[index] = depth;
+				if let Some(ids) = self.id_buffer.as_mut() {
+					ids[index] = self.current_id;
+				}
+			}
+		}
+	}
+
+	/// Writes the current color to the samples of the pixel in `coverage` that pass the
+	/// depth test. `depth` is the depth at `position` and changes by `depth_slope` per pixel,
+	/// so every sample is tested at its own depth.
+	pub fn point_samples(&mut self, x: usize, y: usize, position: Vec2, depth: f32, depth_slope: Vec2, coverage: u32) {
+		if self.resolved {
+			self.point(x, y, depth);
+			return;
+		}
+		let Some(index) = self.index_of(x, y) else {
+			return;
+		};
+
+		let pixel = Vec2::new(x as f32, y as f32);
+		let mut written = false;
+		for (offset, sample) in self.samples_of(index).enumerate() {
+			if coverage & (1 << offset) == 0 {
+				continue;
+			}
+			let sample_depth = depth + depth_slope.dot(&(pixel + self.sample_offsets[offset] - position));
+			if self.sample_depths[sample] > sample_depth {
+				self.sample_colors[sample] = self.current_light;
+				self.sample_emission[sample] = self.current_emission;
+				self.sample_depths[sample] = sample_depth;
+				written = true;
+			}
+		}
+		if written {
+			if let Some(ids) = self.id_buffer.as_mut() {
+				ids[index] = self.current_id;
+			}
+		}
+	}
+
+	// Mixes the current color over the pixel if it passes the depth test. Depth and IDs are
+	// left alone, so blended lines never hide what is drawn after them.
+	pub fn blend_point(&mut self, x: usize, y: usize, depth: f32, alpha: f32) {
+		let Some(index) = self.index_of(x, y) else {
+			return;
+		};
+		if alpha <= 0.0 {
+			return;
+		}
+		let alpha = alpha.min(1.0);
+
+		if !self.resolved {
+			// Mixed as light, in the HDR samples
+			let above = self.current_light;
+			for sample in self.samples_of(index) {
+				if self.sample_depths[sample] > depth {
+					let below = &mut self.sample_colors[sample];
+					for (channel, above) in below.iter_mut().zip(above) {
+						*channel += (above - *channel) * alpha;
+					}
+					// Whatever covers an emissive surface hides part of its glow
+					for channel in self.sample_emission[sample].iter_mut() {
+						*channel *= 1.0 - alpha;
+					}
+				}
+			}
+		} else if self.zbuffer[index] > depth {
+			self.buffer[index] = mix_hex(self.buffer[index], self.current_color, alpha);
+		}
+	}
+
+	/// Overlays (labels, HUD, panels) go over the resolved image in the order they are drawn.
+	/// They skip the depth test and leave the depth buffer alone, so the scene can still be
+	/// queried under them.
+	pub fn overlay_point(&mut self, x: usize, y: usize) {
+		self.overlay_blend(x, y, 1.0);
+	}
+
+	pub fn overlay_blend(&mut self, x: usize, y: usize, alpha: f32) {
+		let Some(index) = self.index_of(x, y) else {
+			return;
+		};
+		if alpha > 0.0 {
+			self.buffer[index] = mix_hex(self.buffer[index], self.current_color, alpha.min(1.0));
+		}
+	}
+
+	fn blend_at(&mut self, x: f32, y: f32, depth: f32, alpha: f32, overlay: bool) {
+		if x < 0.0 || y < 0.0 {
+			return;
+		}
+		if overlay {
+			self.overlay_blend(x as usize, y as usize, alpha);
+		} else {
+			self.blend_point(x as usize, y as usize, depth, alpha);
+		}
+	}
+
+	/// Depth-tested line between two screen positions, with z as the depth. It is blended
+	/// over the framebuffer with `opacity`; antialiased lines split every step between the
+	/// two nearest pixels by coverage.
+	pub fn line_3d(&mut self, start: Vec3, end: Vec3, opacity: f32, antialiased: bool) {
+		self.line(start, end, opacity, antialiased, false);
+	}
+
+	// Same line as an overlay, over everything drawn so far
+	pub fn overlay_line(&mut self, start: Vec2, end: Vec2, opacity: f32, antialiased: bool) {
+		self.line(Vec3::new(start.x, start.y, 0.0), Vec3::new(end.x, end.y, 0.0), opacity, antialiased, true);
+	}
+
+	fn line(&mut self, start: Vec3, end: Vec3, opacity: f32, antialiased: bool, overlay: bool) {
+		let Some((start, end)) = self.clip_to_screen(start, end) else {
+			return;
+		};
+
+		let delta = end - start;
+		let steep = delta.y.abs() > delta.x.abs();
+		let steps = delta.x.abs().max(delta.y.abs()).ceil().max(1.0) as usize;
+
+		for step in 0..=steps {
+			let point = start + delta * (step as f32 / steps as f32);
+
+			if !antialiased {
+				self.blend_at(point.x, point.y, point.z, opacity, overlay);
+			} else if steep {
+				// Pixel centers are at +0.5
+				let left = (point.x - 0.5).floor();
+				let coverage = point.x - 0.5 - left;
+				self.blend_at(left, point.y, point.z, opacity * (1.0 - coverage), overlay);
+				self.blend_at(left + 1.0, point.y, point.z, opacity * coverage, overlay);
+			} else {
+				let top = (point.y - 0.5).floor();
+				let coverage = point.y - 0.5 - top;
+				self.blend_at(point.x, top, point.z, opacity * (1.0 - coverage), overlay);
+				self.blend_at(point.x, top + 1.0, point.z, opacity * coverage, overlay);
+			}
+		}
+	}
+
+	pub fn polyline(&mut self, points: &[Vec3], opacity: f32, antialiased: bool) {
+		for pair in points.windows(2) {
+			self.line_3d(pair[0], pair[1], opacity, antialiased);
+		}
+	}
+
+	// Liang-Barsky clip against the framebuffer, so lines running far off screen stay cheap
+	fn clip_to_screen(&self, start: Vec3, end: Vec3) -> Option<(Vec3, Vec3)> {
+		let delta = end - start;
+		let (mut t_start, mut t_end) = (0.0f32, 1.0f32);
+		let edges = [
+			(-delta.x, start.x),
+			(delta.x, self.width as f32 - start.x),
+			(-delta.y, start.y),
+			(delta.y, self.height as f32 - start.y),
+		];
+
+		for (p, q) in edges {
+			if p == 0.0 {
+				if q < 0.0 {
+					return None; // Parallel to this edge and outside it
+				}
+			} else {
+				let t = q / p;
+				if p < 0.0 {
+					t_start = t_start.max(t);
+				} else {
+					t_end = t_end.min(t);
+				}
 			}
 		}
+
+		if t_start > t_end {
+			None
+		} else {
+			Some((start + delta * t_start, start + delta * t_end))
+		}
+	}
+
+	pub fn save_png(&self, filename: &str) -> image::ImageResult<()> {
+		let mut image = image::RgbImage::new(self.width as u32, self.height as u32);
+		for (pixel, &color) in image.pixels_mut().zip(self.buffer.iter()) {
+			*pixel = image::Rgb([(color >> 16) as u8, (color >> 8) as u8, color as u8]);
+		}
+		image.save(filename)
 	}
 
 	pub fn set_background_color(&mut self, color: u32) {
@@ -44,5 +418,177 @@ impl Framebuffer {
 
 	pub fn set_current_color(&mut self, color: u32) {
 		self.current_color = color;
+		self.current_light = tonemap::hex_to_linear(color);
+		self.current_emission = [0.0; 3];
+	}
+
+	// Shader output, which may be brighter than the screen can show. `emission` is how
+	// strongly the surface gives off its own color as light, for the bloom.
+	pub fn set_current_light(&mut self, color: &Color, emission: f32) {
+		self.current_color = color.to_hex();
+		self.current_light = tonemap::color_to_linear(color);
+		self.current_emission = self.current_light.map(|channel| channel * emission);
+	}
+
+	// Tracks which object wrote each pixel, for pixel-exact picking
+	pub fn enable_id_buffer(&mut self) {
+		self.id_buffer = Some(vec![None; self.width * self.height]);
+	}
+
+	pub fn has_id_buffer(&self) -> bool {
+		self.id_buffer.is_some()
+	}
+
+	pub fn set_current_id(&mut self, id: Option<usize>) {
+		self.current_id = id;
+	}
+
+	// Nearest depth of any sample of the pixel
+	pub fn depth_at(&self, x: usize, y: usize) -> f32 {
+		if x >= self.width || y >= self.height {
+			f32::INFINITY
+		} else if self.resolved {
+			self.zbuffer[y * self.width + x]
+		} else {
+			let samples = self.samples_of(y * self.width + x);
+			self.sample_depths[samples].iter().copied().fold(f32::INFINITY, f32::min)
+		}
+	}
+
+	pub fn id_at(&self, x: usize, y: usize) -> Option<usize> {
+		if x < self.width && y < self.height {
+			self.id_buffer.as_ref()?[y * self.width + x]
+		} else {
+			None
+		}
+	}
+
+	/// Sets every sample in the viewport to `combine(other, self)`, for merging two
+	/// renders of the same view before they are resolved.
+	pub fn combine<F>(&mut self, other: &Framebuffer, viewport: &Viewport, combine: F)
+	where
+		F: Fn([f32; 3], [f32; 3]) -> [f32; 3],
+	{
+		for y in viewport.y..(viewport.y + viewport.height).min(self.height) {
+			for x in viewport.x..(viewport.x + viewport.width).min(self.width) {
+				for sample in self.samples_of(y * self.width + x) {
+					self.sample_colors[sample] = combine(other.sample_colors[sample], self.sample_colors[sample]);
+					self.sample_emission[sample] = combine(other.sample_emission[sample], self.sample_emission[sample]);
+				}
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::viewport::Layout;
+
+	// Clears the layout's viewports and resolves, with a glowing block in view `lit` if given
+	fn resolve_layout(layout: Layout, lit: Option<(usize, usize, usize)>) -> (Vec<u32>, Vec<Viewport>) {
+		let (width, height) = (96, 64);
+		let viewports = layout.viewports(width, height, 0x020208);
+		let mut framebuffer = Framebuffer::new(width, height);
+		for (index, viewport) in viewports.iter().enumerate() {
+			framebuffer.clear_viewport(viewport);
+			framebuffer.set_scissor(Some(*viewport));
+			if let Some((_, x, y)) = lit.filter(|(view, _, _)| *view == index) {
+				framebuffer.set_current_light(&Color::new(255.0, 255.0, 255.0), 50.0);
+				for (dx, dy) in (0..3).flat_map(|dx| (0..3).map(move |dy| (dx, dy))) {
+					framebuffer.point(x + dx, y + dy, 1.0);
+				}
+			}
+		}
+		framebuffer.set_scissor(None);
+		framebuffer.resolve(&ToneMapping::new(), &Bloom::new());
+		(framebuffer.buffer, viewports)
+	}
+
+	// Pixels of `viewport` that the top viewport shows, which differ between the two images
+	fn changed_in(viewport: &Viewport, top: Option<&Viewport>, a: &[u32], b: &[u32]) -> usize {
+		let mut count = 0;
+		for y in viewport.y..viewport.y + viewport.height {
+			for x in viewport.x..viewport.x + viewport.width {
+				let covered = top.is_some_and(|top| top.contains(x as f32, y as f32));
+				if !covered && a[y * 96 + x] != b[y * 96 + x] {
+					count += 1;
+				}
+			}
+		}
+		count
 	}
-}
\ No newline at end of file
+
+	#[test]
+	fn glow_stays_inside_its_viewport() {
+		// Next to the split-screen seam
+		let (dark, views) = resolve_layout(Layout::SplitScreen, None);
+		let (lit, _) = resolve_layout(Layout::SplitScreen, Some((0, 44, 30)));
+		assert_eq!(changed_in(&views[1], None, &dark, &lit), 0);
+		assert!(changed_in(&views[0], None, &dark, &lit) > 9, "the block should glow");
+
+		// Next to the minimap inset, and inside it
+		let (dark, views) = resolve_layout(Layout::PictureInPicture, None);
+		let inset = views[1];
+		let (lit, _) = resolve_layout(Layout::PictureInPicture, Some((0, inset.x - 4, inset.y + 4)));
+		assert_eq!(changed_in(&inset, None, &dark, &lit), 0);
+		let (lit, _) = resolve_layout(Layout::PictureInPicture, Some((1, inset.x, inset.y)));
+		assert_eq!(changed_in(&views[0], Some(&inset), &dark, &lit), 0);
+		assert!(changed_in(&inset, None, &dark, &lit) > 9, "the block should glow");
+	}
+
+	// Resolved colors of a framebuffer with a wall at depth 0.5 over the left half,
+	// and a horizontal line at `depth` across the whole width if given
+	fn wall_and_line(depth: Option<f32>) -> Vec<u32> {
+		let mut framebuffer = Framebuffer::new(16, 8);
+		framebuffer.clear();
+		framebuffer.set_current_color(0x0000FF);
+		for y in 0..8 {
+			for x in 0..8 {
+				framebuffer.point(x, y, 0.5);
+			}
+		}
+		if let Some(depth) = depth {
+			framebuffer.set_current_color(0xFFFFFF);
+			framebuffer.line_3d(Vec3::new(0.5, 4.5, depth), Vec3::new(15.5, 4.5, depth), 1.0, false);
+		}
+		framebuffer.resolve(&ToneMapping::new(), &Bloom { enabled: false, ..Bloom::new() });
+		framebuffer.buffer
+	}
+
+	#[test]
+	fn lines_behind_the_depth_buffer_are_hidden() {
+		let wall = wall_and_line(None);
+		let behind = wall_and_line(Some(0.8));
+		let row = 4 * 16;
+		assert_eq!(behind[row..row + 8], wall[row..row + 8]);
+		assert!(behind[row + 8..row + 16].iter().all(|&color| color == 0xFFFFFF));
+
+		let in_front = wall_and_line(Some(0.2));
+		assert!(in_front[row..row + 16].iter().all(|&color| color == 0xFFFFFF));
+	}
+
+	#[test]
+	fn lines_are_clipped_to_the_screen() {
+		let framebuffer = Framebuffer::new(100, 50);
+		let (start, end) = framebuffer.clip_to_screen(Vec3::new(-1.0e6, 10.0, 0.1), Vec3::new(1.0e6, 30.0, 0.3)).unwrap();
+		assert!((start.x - 0.0).abs() < 1e-3 && (end.x - 100.0).abs() < 1e-3);
+		// The depth is clipped with the line
+		assert!((start.z - 0.2).abs() < 1e-3 && (end.z - 0.2).abs() < 1e-3);
+
+		// Entirely above the screen, beside it, or past a corner
+		assert!(framebuffer.clip_to_screen(Vec3::new(10.0, -5.0, 0.0), Vec3::new(90.0, -1.0, 0.0)).is_none());
+		assert!(framebuffer.clip_to_screen(Vec3::new(120.0, 10.0, 0.0), Vec3::new(200.0, 40.0, 0.0)).is_none());
+		assert!(framebuffer.clip_to_screen(Vec3::new(90.0, -20.0, 0.0), Vec3::new(120.0, 10.0, 0.0)).is_none());
+	}
+
+	#[test]
+	fn off_screen_lines_draw_nothing() {
+		let mut framebuffer = Framebuffer::new(16, 8);
+		framebuffer.clear();
+		framebuffer.set_current_color(0xFFFFFF);
+		framebuffer.polyline(&[Vec3::new(-50.0, -3.0, 0.5), Vec3::new(60.0, -3.0, 0.5), Vec3::new(60.0, 40.0, 0.5)], 1.0, true);
+		framebuffer.resolve(&ToneMapping::new(), &Bloom::new());
+		assert!(framebuffer.buffer.iter().all(|&color| color == 0x000000));
+	}
+}
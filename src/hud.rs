@@ -0,0 +1,184 @@
+use crate::camera::Camera;
+use crate::celestial_body::CelestialBody;
+use crate::framebuffer::Framebuffer;
+use crate::text;
+
+const TEXT_COLOR: u32 = 0xE0E8FF;
+const SHADOW_COLOR: u32 = 0x000000;
+const SCALE: usize = 2;
+const MARGIN: i32 = 8;
+const LINE_SPACING: i32 = 4;
+const FPS_SMOOTHING: f32 = 0.1; // Weight of the newest frame in the FPS average
+
+// Days since the Unix epoch of 2000-01-01, where the simulation starts
+const START_DAY: i64 = 10957;
+
+/// What the HUD reports on, gathered every frame. `target` indexes `bodies`.
+pub struct HudState<'a> {
+    pub camera: &'a Camera,
+    pub bodies: &'a [&'a CelestialBody],
+    pub target: Option<usize>,
+    pub time: f32,
+    pub days_per_tick: f32,
+    pub days_per_second: f32, // Current simulation speed
+}
+
+/// Text drawn over the finished frame: frame rate, date, camera state and target.
+pub struct Hud {
+    pub visible: bool,
+    fps: f32,
+}
+
+/// Days of simulated time per tick, from the first body with both an orbital period and an
+/// orbit speed in the scene. None if no body has them.
+pub fn days_per_tick(bodies: &[&CelestialBody]) -> Option<f32> {
+    bodies
+        .iter()
+        .find(|body| body.info.orbital_period_days > 0.0 && body.orbit_speed != 0.0)
+        .map(|body| body.info.orbital_period_days * body.orbit_speed.abs() / (2.0 * std::f32::consts::PI))
+}
+
+// Year, month and day of a count of days since 1970-01-01 (Howard Hinnant's civil_from_days)
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+// Inverse of civil_from_days
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let shifted_month = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year = (153 * shifted_month + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// "2000-01-01" -> days since the simulation start
+pub fn parse_date(text: &str) -> Option<i64> {
+    let mut parts = text.split('-').map(|part| part.parse::<i64>().ok());
+    let (year, month, day) = (parts.next()??, parts.next()??, parts.next()??);
+    if parts.next().is_some() || !(1..=12).contains(&month) || !(1..=days_in_month(year, month)).contains(&day) {
+        return None;
+    }
+    Some(days_from_civil(year, month, day) - START_DAY)
+}
+
+pub fn format_date(time: f32, days_per_tick: f32) -> String {
+    let (year, month, day) = civil_from_days(START_DAY + (time * days_per_tick).floor() as i64);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+impl Hud {
+    pub fn new() -> Self {
+        Hud { visible: true, fps: 0.0 }
+    }
+
+    pub fn update(&mut self, delta_time: f32) {
+        if delta_time <= 0.0 {
+            return;
+        }
+        let fps = 1.0 / delta_time;
+        self.fps = if self.fps == 0.0 { fps } else { self.fps + (fps - self.fps) * FPS_SMOOTHING };
+    }
+
+    pub fn render(&self, framebuffer: &mut Framebuffer, state: &HudState) {
+        if !self.visible {
+            return;
+        }
+
+        let camera = state.camera;
+        let projection = if camera.projection.is_orthographic() { "orthographic" } else { "perspective" };
+        let mut lines = vec![
+            format!("FPS {:.0}", self.fps),
+            format!("{}  {:.1} days/s", format_date(state.time, state.days_per_tick), state.days_per_second),
+            format!("Camera: {} ({})", camera.mode.name(), projection),
+        ];
+        if let Some(body) = state.target.and_then(|index| state.bodies.get(index)) {
+            let distance = (body.get_position(state.time) - camera.eye).magnitude();
+            lines.push(format!("Target: {}  {:.1} u", body.name, distance));
+        }
+
+        let line_height = (text::GLYPH_HEIGHT * SCALE) as i32 + LINE_SPACING;
+        for (index, line) in lines.iter().enumerate() {
+            let y = MARGIN + index as i32 * line_height;
+            // A drop shadow keeps the text readable over bright bodies
+            text::draw_text(framebuffer, MARGIN + 1, y + 1, line, SHADOW_COLOR, SCALE, None);
+            text::draw_text(framebuffer, MARGIN, y, line, TEXT_COLOR, SCALE, None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_dates_round_trip() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(START_DAY), (2000, 1, 1));
+        assert_eq!(days_from_civil(2000, 1, 1), START_DAY);
+        // Leap days, including the 400-year rule
+        assert_eq!(civil_from_days(days_from_civil(2000, 2, 29)), (2000, 2, 29));
+        assert_eq!(days_from_civil(1900, 3, 1) - days_from_civil(1900, 2, 28), 1);
+        assert_eq!(days_from_civil(2024, 3, 1) - days_from_civil(2024, 2, 28), 2);
+    }
+
+    #[test]
+    fn every_day_of_four_centuries_round_trips() {
+        for days in -80000..80000 {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+    }
+
+    #[test]
+    fn dates_parse_relative_to_the_start() {
+        assert_eq!(parse_date("2000-01-01"), Some(0));
+        assert_eq!(parse_date("2000-01-31"), Some(30));
+        assert_eq!(parse_date("1999-12-31"), Some(-1));
+        assert_eq!(parse_date("2001-01-01"), Some(366));
+    }
+
+    #[test]
+    fn malformed_dates_are_rejected() {
+        for text in ["", "2000", "2000-01", "2000-13-01", "2000-00-10", "2000-01-32", "2000-01-01-01", "2000-aa-01"] {
+            assert_eq!(parse_date(text), None, "{}", text);
+        }
+    }
+
+    #[test]
+    fn days_past_the_end_of_the_month_are_rejected() {
+        for text in ["2000-02-31", "2000-02-30", "2001-02-29", "1900-02-29", "2000-04-31", "2000-11-31"] {
+            assert_eq!(parse_date(text), None, "{}", text);
+        }
+        assert_eq!(parse_date("2000-02-29"), Some(59));
+        assert_eq!(parse_date("2004-02-29").map(|day| day - parse_date("2004-02-28").unwrap()), Some(1));
+        assert_eq!(parse_date("2000-12-31"), Some(365));
+    }
+
+    #[test]
+    fn dates_format_from_simulation_time() {
+        assert_eq!(format_date(0.0, 1.0), "2000-01-01");
+        assert_eq!(format_date(10.0, 0.5), "2000-01-06");
+        assert_eq!(format_date(366.0, 1.0), "2001-01-01");
+    }
+}
@@ -0,0 +1,88 @@
+use crate::celestial_body::CelestialBody;
+use crate::framebuffer::Framebuffer;
+use crate::text;
+
+const PANEL_WIDTH: usize = 220;
+const MARGIN: usize = 8;
+const PADDING: usize = 8;
+const LINE_SPACING: usize = 4;
+const BACKGROUND_COLOR: u32 = 0x101828;
+const BACKGROUND_OPACITY: f32 = 0.75;
+const TITLE_COLOR: u32 = 0xFFFFFF;
+const TEXT_COLOR: u32 = 0xC8D2E8;
+
+// Splits the text into lines of at most `width` characters, breaking between words
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.len() + 1 + word.len() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+fn data_lines(body: &CelestialBody) -> Vec<String> {
+    let info = &body.info;
+    let period = if info.orbital_period_days > 0.0 {
+        format!("{:.2} days", info.orbital_period_days)
+    } else {
+        String::from("-")
+    };
+
+    vec![
+        format!("Radius: {:.0} km", info.radius_km),
+        format!("Mass: {:.3e} kg", info.mass_kg),
+        format!("Orbit: {}", period),
+        format!("Day: {:.2} h", info.day_length_hours),
+        format!("Mean temp: {:.0} °C", info.mean_temperature_c),
+        format!("Moons: {}", info.moons),
+    ]
+}
+
+/// Draws the data of the body in a translucent box in the top-right corner, over everything.
+pub fn render(framebuffer: &mut Framebuffer, body: Option<&CelestialBody>) {
+    let line_height = text::GLYPH_HEIGHT + LINE_SPACING;
+    let columns = (PANEL_WIDTH - 2 * PADDING) / (text::GLYPH_WIDTH + 1);
+
+    let (title, lines) = match body {
+        Some(body) => {
+            let mut lines = data_lines(body);
+            if !body.info.description.is_empty() {
+                lines.push(String::new());
+                lines.extend(wrap(&body.info.description, columns));
+            }
+            (body.name.as_str(), lines)
+        }
+        None => ("No body selected", wrap("Click a body or press 1-5 to select it.", columns)),
+    };
+
+    let title_height = text::GLYPH_HEIGHT * 2 + LINE_SPACING * 2;
+    let height = 2 * PADDING + title_height + lines.len() * line_height;
+    let x = framebuffer.width.saturating_sub(PANEL_WIDTH + MARGIN);
+    let y = MARGIN;
+
+    framebuffer.set_current_color(BACKGROUND_COLOR);
+    for py in y..(y + height).min(framebuffer.height) {
+        for px in x..(x + PANEL_WIDTH).min(framebuffer.width) {
+            framebuffer.overlay_blend(px, py, BACKGROUND_OPACITY);
+        }
+    }
+
+    let text_x = (x + PADDING) as i32;
+    let mut text_y = (y + PADDING) as i32;
+    text::draw_text(framebuffer, text_x, text_y, title, TITLE_COLOR, 2, None);
+    text_y += title_height as i32;
+    for line in &lines {
+        text::draw_text(framebuffer, text_x, text_y, line, TEXT_COLOR, 1, None);
+        text_y += line_height as i32;
+    }
+}
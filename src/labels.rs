@@ -0,0 +1,178 @@
+use nalgebra_glm::{Mat4, Vec2, Vec3, Vec4};
+use crate::celestial_body::CelestialBody;
+use crate::framebuffer::Framebuffer;
+use crate::text;
+
+const TEXT_COLOR: u32 = 0xFFFFFF;
+const SHADOW_COLOR: u32 = 0x000000;
+const LEADER_LENGTH: f32 = 18.0;
+const EDGE_MARGIN: f32 = 10.0;
+const MARKER_SIZE: f32 = 6.0;
+const PADDING: i32 = 2;
+const PLACEMENT_TRIES: i32 = 8;
+const DEPTH_TOLERANCE: f32 = 1e-3;
+
+struct Rect {
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+}
+
+impl Rect {
+    fn overlaps(&self, other: &Rect) -> bool {
+        self.x < other.x + other.width && other.x < self.x + self.width &&
+        self.y < other.y + other.height && other.y < self.y + self.height
+    }
+}
+
+fn draw_name(framebuffer: &mut Framebuffer, rect: &Rect, name: &str) {
+    text::draw_text(framebuffer, rect.x + 1, rect.y + 1, name, SHADOW_COLOR, 1, None);
+    text::draw_text(framebuffer, rect.x, rect.y, name, TEXT_COLOR, 1, None);
+}
+
+fn project(view_projection: &Mat4, viewport_matrix: &Mat4, point: Vec3) -> Vec4 {
+    let clip = view_projection * Vec4::new(point.x, point.y, point.z, 1.0);
+    let w = clip.w;
+    let screen = viewport_matrix * Vec4::new(clip.x / w, clip.y / w, clip.z / w, 1.0);
+    Vec4::new(screen.x, screen.y, screen.z, w)
+}
+
+// Moves the rectangle up and down until it stops overlapping the placed ones
+fn place(rect: Rect, placed: &[Rect]) -> Rect {
+    let step = rect.height + PADDING;
+    for attempt in 0..PLACEMENT_TRIES {
+        // 0, -1, +1, -2, +2, ... steps away from where it wanted to be
+        let offset = if attempt % 2 == 0 { -(attempt / 2) } else { attempt / 2 + 1 };
+        let candidate = Rect { y: rect.y + offset * step, ..rect };
+        if !placed.iter().any(|other| candidate.overlaps(other)) {
+            return candidate;
+        }
+    }
+    rect
+}
+
+/// Names every body next to it on screen, with a leader line from its edge. Bodies hidden
+/// behind others are skipped; bodies outside the view get a marker on the viewport edge.
+/// Drawn as an overlay after the frame is resolved, since occlusion is read from the z-buffer.
+pub fn render_labels(
+    framebuffer: &mut Framebuffer,
+    bodies: &[&CelestialBody],
+    view_matrix: &Mat4,
+    projection_matrix: &Mat4,
+    viewport_matrix: &Mat4,
+    time: f32,
+) {
+    let view_projection = projection_matrix * view_matrix;
+    let left = viewport_matrix[(0, 3)] - viewport_matrix[(0, 0)];
+    let top = viewport_matrix[(1, 3)] + viewport_matrix[(1, 1)];
+    let right = left + 2.0 * viewport_matrix[(0, 0)];
+    let bottom = top - 2.0 * viewport_matrix[(1, 1)];
+    let center = Vec3::new((left + right) / 2.0, (top + bottom) / 2.0, 0.0);
+
+    let eye = view_matrix.try_inverse().map(|inverse| inverse.column(3).xyz()).unwrap_or(Vec3::zeros());
+    let camera_right = Vec3::new(view_matrix[(0, 0)], view_matrix[(0, 1)], view_matrix[(0, 2)]);
+
+    // Nearest bodies get first pick of label positions
+    let mut order: Vec<usize> = (0..bodies.len()).collect();
+    order.sort_by(|&a, &b| {
+        let distance_a = (bodies[a].get_position(time) - eye).magnitude();
+        let distance_b = (bodies[b].get_position(time) - eye).magnitude();
+        distance_a.total_cmp(&distance_b)
+    });
+
+    framebuffer.set_current_id(None);
+    let mut placed: Vec<Rect> = Vec::new();
+    for index in order {
+        let body = bodies[index];
+        let position = body.get_position(time);
+        let radius = body.bounding_radius();
+        let screen = project(&view_projection, viewport_matrix, position);
+        let (text_width, text_height) = text::text_size(&body.name, 1);
+        let (text_width, text_height) = (text_width as i32, text_height as i32);
+
+        let on_screen = screen.w > 0.0 && screen.x >= left && screen.x < right && screen.y >= top && screen.y < bottom;
+        if !on_screen {
+            // Direction towards the body; flipped when it is behind the camera
+            let mut direction = Vec3::new(screen.x, screen.y, 0.0) - center;
+            if screen.w < 0.0 {
+                direction = -direction;
+            }
+            if direction.magnitude() < 1e-3 {
+                direction = Vec3::new(0.0, 1.0, 0.0);
+            }
+            let direction = direction.normalize();
+
+            // Scale the direction until it reaches the inset viewport border
+            let half_width = (right - left) / 2.0 - EDGE_MARGIN;
+            let half_height = (bottom - top) / 2.0 - EDGE_MARGIN;
+            let reach = (half_width / direction.x.abs().max(1e-6)).min(half_height / direction.y.abs().max(1e-6));
+            let tip = center + direction * reach;
+
+            // Small arrowhead pointing at the body
+            let side = Vec3::new(-direction.y, direction.x, 0.0);
+            let base = tip - direction * MARKER_SIZE;
+            let corners = [tip, base + side * MARKER_SIZE * 0.6, base - side * MARKER_SIZE * 0.6, tip];
+            framebuffer.set_current_color(body.orbit_color);
+            for pair in corners.windows(2) {
+                framebuffer.overlay_line(pair[0].xy(), pair[1].xy(), 1.0, true);
+            }
+
+            // Name just inside the arrow, kept within the viewport. Not `clamp`, which panics
+            // when the viewport is smaller than the label; the left and top edges win then.
+            let label = base - direction * (text_width.max(text_height) as f32 / 2.0 + 4.0);
+            let x = (label.x as i32 - text_width / 2).min(right as i32 - text_width).max(left as i32);
+            let y = (label.y as i32 - text_height / 2).min(bottom as i32 - text_height).max(top as i32);
+            let rect = place(Rect { x, y, width: text_width, height: text_height }, &placed);
+            draw_name(framebuffer, &rect, &body.name);
+            placed.push(rect);
+            continue;
+        }
+
+        // Hidden if something is in front of the point of the sphere nearest to the camera
+        let towards_eye = (eye - position).normalize();
+        let front = project(&view_projection, viewport_matrix, position + towards_eye * radius);
+        if front.x >= 0.0 && front.y >= 0.0 && framebuffer.depth_at(front.x as usize, front.y as usize) < front.z - DEPTH_TOLERANCE {
+            continue;
+        }
+
+        // Leader line from the edge of the body up and to the right
+        let edge = project(&view_projection, viewport_matrix, position + camera_right * radius);
+        let screen_radius = (edge.x - screen.x).hypot(edge.y - screen.y);
+        let diagonal = Vec3::new(1.0, -1.0, 0.0).normalize();
+        let start = Vec3::new(screen.x, screen.y, 0.0) + diagonal * screen_radius;
+        let end = start + diagonal * LEADER_LENGTH;
+
+        let rect = place(
+            Rect { x: end.x as i32 + PADDING, y: end.y as i32 - text_height, width: text_width, height: text_height },
+            &placed,
+        );
+        // The leader meets the label at its bottom-left corner, wherever it was moved to
+        let corner = Vec2::new((rect.x - PADDING) as f32, (rect.y + rect.height) as f32);
+
+        framebuffer.set_current_color(body.orbit_color);
+        framebuffer.overlay_line(start.xy(), corner, 1.0, true);
+        draw_name(framebuffer, &rect, &body.name);
+        placed.push(rect);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::celestial_body::still_body;
+    use crate::{create_perspective_matrix, create_view_matrix, create_viewport_matrix};
+
+    #[test]
+    fn off_screen_label_wider_than_the_viewport_does_not_panic() {
+        let body = still_body("A name far wider than the viewport", Vec::new());
+        let (width, height) = (40.0, 20.0);
+        let mut framebuffer = Framebuffer::new(width as usize, height as usize);
+        // The body is behind the camera, so it gets an edge marker
+        let view = create_view_matrix(Vec3::new(0.0, 0.0, -10.0), Vec3::new(0.0, 0.0, -20.0), Vec3::new(0.0, 1.0, 0.0));
+        let projection = create_perspective_matrix(width, height, 1.0);
+        let viewport = create_viewport_matrix(width, height);
+
+        render_labels(&mut framebuffer, &[&body], &view, &projection, &viewport, 0.0);
+    }
+}
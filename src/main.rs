@@ -1,6 +1,6 @@
-use nalgebra_glm::{Vec3, Mat4, look_at, perspective};
+use nalgebra_glm::{Vec3, Mat4, look_at, perspective, ortho};
 use minifb::{Key, Window, WindowOptions};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::f32::consts::PI;
 
 mod framebuffer;
@@ -12,24 +12,92 @@ mod fragment;
 mod shaders;
 mod camera;
 mod celestial_body;
+mod mouse;
+mod picking;
+mod camera_path;
+mod warp;
+mod projection;
+mod bookmarks;
+mod viewport;
+mod stereo;
+mod panorama;
+mod background;
+mod star_catalog;
+mod orbits;
+mod text;
+mod hud;
+mod labels;
+mod scene;
+mod info_panel;
+mod poster;
+mod recording;
+mod cli;
+mod display;
+mod antialiasing;
+mod tonemap;
+mod bloom;
+mod file_format;
+mod clock;
 
 use framebuffer::Framebuffer;
 use vertex::Vertex;
-use obj::Obj;
 use triangle::triangle;
 use shaders::{vertex_shader, fragment_shader_neptune, fragment_shader_jupiter, fragment_shader_venus, fragment_shader_mars, fragment_shader_earth, fragment_shader_mercury, fragment_shader_sun};
 use camera::Camera;
 use celestial_body::CelestialBody;
+use mouse::MouseState;
+use camera_path::{CameraPath, Interpolation, PathController};
+use warp::Obstacle;
+use bookmarks::{Bookmark, Bookmarks};
+use viewport::{Layout, Viewport};
+use stereo::StereoSettings;
+use background::Background;
+use star_catalog::SkyOverlay;
+use hud::{Hud, HudState};
+use scene::Scene;
+use recording::{RecordFormat, Recorder};
+use cli::Args;
+use display::Display;
+use antialiasing::Antialiasing;
+use tonemap::ToneMapping;
+use bloom::Bloom;
+use clock::SimulationClock;
 
 pub struct Uniforms {
     model_matrix: Mat4,
     view_matrix: Mat4,
     projection_matrix: Mat4,
     viewport_matrix: Mat4,
-    time: u32,
+    time: f32,
     sun_position: Vec3,
 }
 
+// Optional overlays, toggled at runtime
+#[derive(Clone, Copy)]
+pub struct RenderOptions {
+    sky_overlay: SkyOverlay,
+    orbits: bool,
+    trails: bool,
+    antialiased_lines: bool,
+    labels: bool,
+    tone_mapping: ToneMapping,
+    bloom: Bloom,
+}
+
+impl RenderOptions {
+    fn new() -> Self {
+        RenderOptions {
+            sky_overlay: SkyOverlay::Off,
+            orbits: false,
+            trails: false,
+            antialiased_lines: true,
+            labels: false,
+            tone_mapping: ToneMapping::new(),
+            bloom: Bloom::new(),
+        }
+    }
+}
+
 fn create_model_matrix(translation: Vec3, scale: f32, rotation: Vec3) -> Mat4 {
     let (sin_x, cos_x) = rotation.x.sin_cos();
     let (sin_y, cos_y) = rotation.y.sin_cos();
@@ -72,224 +140,703 @@ fn create_view_matrix(eye: Vec3, center: Vec3, up: Vec3) -> Mat4 {
     look_at(&eye, &center, &up)
 }
 
-fn create_perspective_matrix(window_width: f32, window_height: f32) -> Mat4 {
-    let fov = 45.0 * PI / 180.0;
+fn create_perspective_matrix(window_width: f32, window_height: f32, fov: f32) -> Mat4 {
     let aspect_ratio = window_width / window_height;
     let near = 0.1;
     let far = 1000.0;
 
-    perspective(fov, aspect_ratio, near, far)
+    // nalgebra-glm's perspective takes (aspect, fovy, near, far)
+    perspective(aspect_ratio, fov, near, far)
+}
+
+fn create_orthographic_matrix(window_width: f32, window_height: f32, view_height: f32) -> Mat4 {
+    let aspect_ratio = window_width / window_height;
+    let half_height = view_height / 2.0;
+    let half_width = half_height * aspect_ratio;
+    let near = 0.1;
+    let far = 1000.0;
+
+    ortho(-half_width, half_width, -half_height, half_height, near, far)
 }
 
 fn create_viewport_matrix(width: f32, height: f32) -> Mat4 {
+    create_viewport_matrix_at(0.0, 0.0, width, height)
+}
+
+// Maps NDC into a sub-rectangle of the framebuffer starting at (x, y)
+fn create_viewport_matrix_at(x: f32, y: f32, width: f32, height: f32) -> Mat4 {
     Mat4::new(
-        width / 2.0, 0.0, 0.0, width / 2.0,
-        0.0, -height / 2.0, 0.0, height / 2.0,
+        width / 2.0, 0.0, 0.0, x + width / 2.0,
+        0.0, -height / 2.0, 0.0, y + height / 2.0,
         0.0, 0.0, 1.0, 0.0,
         0.0, 0.0, 0.0, 1.0
     )
 }
 
+// Matrices for drawing one view. The sky has its own projection, since orthographic
+// views still draw it in perspective.
+#[derive(Clone, Copy)]
+struct ViewMatrices {
+    view_matrix: Mat4,
+    projection_matrix: Mat4,
+    sky_projection_matrix: Mat4,
+    viewport_matrix: Mat4,
+}
+
+impl ViewMatrices {
+    fn new(view_matrix: Mat4, projection_matrix: Mat4, viewport_matrix: Mat4) -> Self {
+        let width = 2.0 * viewport_matrix[(0, 0)];
+        let height = -2.0 * viewport_matrix[(1, 1)];
+        ViewMatrices {
+            view_matrix,
+            projection_matrix,
+            sky_projection_matrix: background::sky_projection(&projection_matrix, width, height),
+            viewport_matrix,
+        }
+    }
+}
+
+// What a frame is drawn from, apart from the camera and the time
+#[derive(Clone, Copy)]
+struct SceneContext<'a> {
+    planets: &'a [CelestialBody],
+    sun: &'a CelestialBody,
+    background: &'a Background,
+    options: RenderOptions,
+}
+
+fn render_scene(framebuffer: &mut Framebuffer, context: &SceneContext, matrices: &ViewMatrices, time: f32) {
+    let SceneContext { planets, sun, background, options } = *context;
+    let ViewMatrices { view_matrix, projection_matrix, sky_projection_matrix, viewport_matrix } = *matrices;
+    let sun_world_position = Vec3::new(0.0, 0.0, 0.0);
+
+    background.render(framebuffer, &view_matrix, &sky_projection_matrix, &viewport_matrix);
+    star_catalog::render_overlay(framebuffer, options.sky_overlay, &view_matrix, &sky_projection_matrix, &viewport_matrix);
+
+    for (index, planet) in planets.iter().enumerate() {
+        let model_matrix = planet.get_model_matrix(time);
+        let uniforms = Uniforms {
+            model_matrix,
+            view_matrix,
+            projection_matrix,
+            viewport_matrix,
+            time,
+            sun_position: sun_world_position,
+        };
+        framebuffer.set_current_id(Some(index));
+        planet.render(framebuffer, &uniforms);
+    }
+
+    // Render the Sun separately (it doesn't orbit).
+    let sun_model_matrix = sun.get_model_matrix(time);
+    let sun_uniforms = Uniforms {
+        model_matrix: sun_model_matrix,
+        view_matrix,
+        projection_matrix,
+        viewport_matrix,
+        time,
+        sun_position: sun_world_position,
+    };
+    framebuffer.set_current_id(Some(planets.len()));
+    sun.render(framebuffer, &sun_uniforms);
+
+    // Lines go last so the depth test hides them behind the bodies
+    let view_projection = projection_matrix * view_matrix;
+    for planet in planets {
+        if options.orbits {
+            orbits::render_orbit(framebuffer, planet, &view_projection, &viewport_matrix, options.antialiased_lines);
+        }
+        if options.trails {
+            orbits::render_trail(framebuffer, planet, &view_projection, &viewport_matrix, time, options.antialiased_lines);
+        }
+    }
+}
+
+// Plays the camera path (if any) without opening a window, recording it at a fixed
+// frame rate. The output is a directory of PNGs, or a .y4m or .gif file.
+fn render_headless(
+    args: &Args,
+    output: &str,
+    path: &CameraPath,
+    mut camera: Camera,
+    context: &SceneContext,
+    start_time: f32,
+) -> std::io::Result<()> {
+    let (width, height) = args.framebuffer_size();
+    let frame_time = 1.0 / args.fps as f32;
+    let frame_count = match args.frames {
+        Some(frames) => frames,
+        None if !path.keyframes.is_empty() => (path.duration() / frame_time).ceil() as u32 + 1,
+        None => {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "give --frames or a --path to record"));
+        }
+    };
+
+    let mut recorder = Recorder::start(output, RecordFormat::from_path(output), width, height, args.fps)?;
+    let mut framebuffer = Framebuffer::new(width, height);
+    framebuffer.set_background_color(context.background.clear_color());
+    framebuffer.set_antialiasing(args.antialiasing);
+    let viewport_matrix = create_viewport_matrix(width as f32, height as f32);
+    let options = &context.options;
+
+    for frame in 0..frame_count {
+        let seconds = frame as f32 * frame_time;
+        if let Some(keyframe) = path.sample(seconds) {
+            keyframe.apply(&mut camera);
+        }
+
+        let view_matrix = create_view_matrix(camera.eye, camera.center, camera.up);
+        let projection_matrix = camera.projection.matrix(width as f32, height as f32);
+        let time = start_time + seconds * args.time_scale / SIMULATION_STEP;
+
+        framebuffer.clear();
+        render_scene(&mut framebuffer, context, &ViewMatrices::new(view_matrix, projection_matrix, viewport_matrix), time);
+        framebuffer.resolve(&options.tone_mapping, &options.bloom);
+        recorder.record_frame(&framebuffer)?;
+    }
+
+    recorder.finish()
+}
+
+// Starting view: the start of the camera path, else the bookmark, else the default
+fn initial_camera(path: &CameraPath, bookmark: Option<&Bookmark>) -> Camera {
+    let mut camera = Camera::new(
+        Vec3::new(0.0, 0.0, 20.0),
+        Vec3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0)
+    );
+    if let Some(keyframe) = path.sample(0.0) {
+        keyframe.apply(&mut camera);
+    } else if let Some(bookmark) = bookmark {
+        bookmark.apply(&mut camera);
+    }
+    camera
+}
+
+fn export_panorama(filename: &str, camera: &Camera, context: &SceneContext, time: f32) -> image::ImageResult<()> {
+    let options = &context.options;
+    panorama::export(filename, camera, context.background.clear_color(), |framebuffer, view_matrix, projection_matrix, viewport| {
+        render_scene(framebuffer, context, &ViewMatrices::new(view_matrix, projection_matrix, viewport.matrix()), time);
+        framebuffer.resolve(&options.tone_mapping, &options.bloom);
+    })?;
+    println!("Saved panorama to {}", filename);
+    Ok(())
+}
+
+fn export_poster(filename: &str, camera: &Camera, size: (usize, usize), context: &SceneContext, time: f32) -> image::ImageResult<()> {
+    let options = &context.options;
+    println!("Rendering {}x{} poster...", size.0, size.1);
+    poster::export(filename, camera, size.0, size.1, options.bloom.reach(), context.background.clear_color(), |framebuffer, matrices| {
+        render_scene(framebuffer, context, matrices, time);
+        framebuffer.resolve(&options.tone_mapping, &options.bloom);
+    })?;
+    println!("Saved poster to {}", filename);
+    Ok(())
+}
+
+// Framebuffer at the internal resolution of the window, with picking enabled
+fn window_framebuffer(display: &Display, background: &Background, antialiasing: Antialiasing) -> Framebuffer {
+    let (width, height) = display.framebuffer_size();
+    let mut framebuffer = Framebuffer::new(width, height);
+    framebuffer.set_background_color(background.clear_color());
+    framebuffer.set_antialiasing(antialiasing);
+    framebuffer.enable_id_buffer();
+    framebuffer
+}
+
+// Milliseconds since the Unix epoch, for unique output filenames
+fn timestamp() -> u128 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_millis())
+        .unwrap_or(0)
+}
+
+const SIMULATION_STEP: f32 = 1.0 / 60.0; // Seconds per simulation tick
 
 fn main() {
-    let window_width = 600;
-    let window_height = 600;
-    let framebuffer_width = 600;
-    let framebuffer_height = 600;
-    let frame_delay = Duration::from_millis(16);
+    let raw_args: Vec<String> = std::env::args().skip(1).collect();
+    let args = Args::parse(&raw_args).unwrap_or_else(|e| {
+        eprintln!("{}\n\n{}", e, cli::USAGE);
+        std::process::exit(2);
+    });
+    if args.help {
+        print!("{}", cli::USAGE);
+        return;
+    }
+
+    let frame_delay = Duration::from_secs_f32(1.0 / args.fps as f32);
+
+    let Scene { planets, sun } = Scene::load(&args.scene_file).unwrap_or_else(|e| {
+        eprintln!("Could not load scene: {}", e);
+        std::process::exit(1);
+    });
+
+    let background = match (&args.sky_file, &args.skybox_stem) {
+        (Some(filename), _) => Background::load_equirectangular(filename),
+        (None, Some(stem)) => Background::load_cubemap(stem),
+        (None, None) if args.no_stars => Ok(Background::Flat),
+        (None, None) => Ok(Background::starfield(args.seed)),
+    }
+    .unwrap_or_else(|e| {
+        eprintln!("Could not load sky image: {}", e);
+        std::process::exit(1);
+    });
+
+    let camera_path = match &args.path_file {
+        Some(filename) => CameraPath::load(filename).unwrap_or_else(|e| {
+            eprintln!("Could not load camera path {}: {}", filename, e);
+            std::process::exit(1);
+        }),
+        None => CameraPath::new(Interpolation::CatmullRom),
+    };
+
+    let mut bookmarks = match Bookmarks::load(BOOKMARKS_FILE) {
+        Ok(bookmarks) => bookmarks,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Bookmarks::new(),
+        Err(e) => {
+            eprintln!("Could not load bookmarks: {}", e);
+            Bookmarks::new()
+        }
+    };
+    let start_bookmark = args.bookmark.as_ref().map(|name| {
+        bookmarks.get(name).cloned().unwrap_or_else(|| {
+            eprintln!("No bookmark named '{}' in {}", name, BOOKMARKS_FILE);
+            std::process::exit(1);
+        })
+    });
+
+    // Pickable bodies; IDs written to the framebuffer index into this list
+    let bodies: Vec<&CelestialBody> = planets.iter().chain(std::iter::once(&sun)).collect();
+    let days_per_tick = hud::days_per_tick(&bodies).unwrap_or_else(|| {
+        println!("No body in {} has an orbital period, so each tick is one day", args.scene_file);
+        1.0
+    });
+
+    // An explicit date wins over the time saved in the bookmark
+    let start_time = match (args.start_day, &start_bookmark) {
+        (Some(day), _) => (day as f32 / days_per_tick).round() as u32,
+        (None, Some(bookmark)) => bookmark.time,
+        (None, None) => 0,
+    };
+    let mut options = RenderOptions { tone_mapping: args.tone_mapping, bloom: args.bloom, ..RenderOptions::new() };
+    let context = SceneContext { planets: &planets, sun: &sun, background: &background, options };
+
+    if let Some(filename) = &args.panorama_file {
+        let camera = initial_camera(&camera_path, start_bookmark.as_ref());
+        if let Err(e) = export_panorama(filename, &camera, &context, start_time as f32) {
+            eprintln!("Panorama export failed: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(filename) = &args.poster_file {
+        let camera = initial_camera(&camera_path, start_bookmark.as_ref());
+        if let Err(e) = export_poster(filename, &camera, args.poster_size, &context, start_time as f32) {
+            eprintln!("Poster export failed: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(output) = &args.headless {
+        let camera = initial_camera(&camera_path, start_bookmark.as_ref());
+        if let Err(e) = render_headless(&args, output, &camera_path, camera, &context, start_time as f32) {
+            eprintln!("Headless render failed: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
 
-    let mut framebuffer = Framebuffer::new(framebuffer_width, framebuffer_height);
     let mut window = Window::new(
         "Rust 3D model",
-        window_width,
-        window_height,
-        WindowOptions::default(),
+        args.window_width,
+        args.window_height,
+        WindowOptions { resize: true, ..WindowOptions::default() },
     )
     .unwrap();
 
     window.set_position(0,0);
     window.update();
 
-    framebuffer.set_background_color(0x333355);
-
-
-    let planets = vec![
-        CelestialBody {
-            name: String::from("Earth"),
-            vertex_array: Obj::load("./assets/models/sphere.obj").unwrap().get_vertex_array(),
-            shader_type: 6,
-            orbit_radius: 4.0,
-            rotation_speed: 0.05,
-            orbit_speed: 0.015,
-            scale: 1.0,
-        },
-        CelestialBody {
-            name: String::from("Mars"),
-            vertex_array: Obj::load("./assets/models/sphere.obj").unwrap().get_vertex_array(),
-            shader_type: 5,
-            orbit_radius: 5.0,
-            rotation_speed: 0.045,
-            orbit_speed: 0.0095,
-            scale: 0.8,
-        },
-        CelestialBody {
-            name: String::from("Jupiter"),
-            vertex_array: Obj::load("./assets/models/sphere.obj").unwrap().get_vertex_array(),
-            shader_type: 1,
-            orbit_radius: 7.0,
-            rotation_speed: 0.07,
-            orbit_speed: 0.0085,
-            scale: 1.5,
-        },
-        CelestialBody {
-            name: String::from("Venus"),
-            vertex_array: Obj::load("./assets/models/sphere.obj").unwrap().get_vertex_array(),
-            shader_type: 4,
-            orbit_radius: 3.0,
-            rotation_speed: 0.01,
-            orbit_speed: 0.03,
-            scale: 0.9,
-        },
-        CelestialBody {
-            name: String::from("Mercury"),
-            vertex_array: Obj::load("./assets/models/sphere.obj").unwrap().get_vertex_array(),
-            shader_type: 7,
-            orbit_radius: 2.0,
-            rotation_speed: 0.02,
-            orbit_speed: 0.05,
-            scale: 0.5,
-        },
-    ];
-
-    let sun = CelestialBody {
-        name: String::from("Sun"),
-        vertex_array: Obj::load("./assets/models/sphere.obj").unwrap().get_vertex_array(),
-        shader_type: 8,
-        orbit_radius: 0.0,  // Doesn't orbit
-        rotation_speed: 0.01,
-        orbit_speed: 0.0,
-        scale: 2.5,
-    };
+    let mut display = Display::new(args.window_width, args.window_height, args.scale, args.filter);
+    let mut framebuffer = window_framebuffer(&display, &background, args.antialiasing);
 
 
+    let mut camera = initial_camera(&camera_path, start_bookmark.as_ref());
 
-    let mut camera = Camera::new(
+    let mut mouse = MouseState::new();
+    let mut path_controller = PathController::new(camera_path);
+    if args.path_file.is_some() {
+        path_controller.toggle_playback();
+    }
+
+    // Secondary cameras for the minimap inset and the split-screen comparison
+    let mut minimap_camera = Camera::new(
+        Vec3::new(0.0, 20.0, 0.0),
+        Vec3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, -1.0)
+    );
+    minimap_camera.switch_to_birds_eye(scene_radius(&bodies));
+    let mut compare_camera = Camera::new(
         Vec3::new(0.0, 0.0, 20.0),
         Vec3::new(0.0, 0.0, 0.0),
         Vec3::new(0.0, 1.0, 0.0)
     );
-
-
-    let mut time = 0;
-    let mut shader_type = 0;
-
-    let projection_matrix = create_perspective_matrix(window_width as f32, window_height as f32);
-    let viewport_matrix = create_viewport_matrix(framebuffer_width as f32, framebuffer_height as f32);
-    let sun_world_position = Vec3::new(0.0, 0.0, 0.0);
+    compare_camera.projection.set_fov(30.0 * PI / 180.0);
+    let mut compare_index = 2.min(bodies.len().saturating_sub(1)); // The third body in the scene, or the last one in smaller scenes
+    let mut layout = Layout::Single;
+    let mut stereo_settings = StereoSettings::new();
+    let mono_settings = StereoSettings::new();
+    let mut eye_buffer = None;
+    let mut hud = Hud::new();
+    let mut target = None; // Body the camera last warped to
+    let mut show_info = false;
+    let mut record_format = RecordFormat::PngSequence;
+    let mut recorder: Option<Recorder> = None;
+
+    let mut clock = SimulationClock::new(start_time);
+
+    let mut last_frame = Instant::now();
     while window.is_open() {
         if window.is_key_down(Key::Escape) {
             break;
         }
 
-        
-        
-        time += 1;
+        let frame_start = Instant::now();
+        let delta_time = clock::frame_seconds((frame_start - last_frame).as_secs_f32(), recorder.as_ref().map(|recorder| recorder.fps));
+        last_frame = frame_start;
 
-        framebuffer.clear();
+        // The orbits advance in fixed ticks regardless of how fast we render
+        clock.advance(delta_time * args.time_scale);
+        let render_time = clock.render_time();
 
-        handle_input(&window, &mut camera, &planets);
-        let delta_time = frame_delay.as_secs_f32();
-        camera.update(delta_time);
+        camera.colliders = body_obstacles(&bodies, render_time);
 
-        let view_matrix = create_view_matrix(camera.eye, camera.center, camera.up);
-        for planet in &planets {
-            let model_matrix = planet.get_model_matrix(time as f32);
-            let uniforms = Uniforms {
-                model_matrix,
-                view_matrix,
-                projection_matrix,
-                viewport_matrix,
-                time,
-                sun_position: sun_world_position,
-            };
-            planet.render(&mut framebuffer, &uniforms);
-        }
-
-        // Render the Sun separately (it doesn't orbit).
-        let sun_model_matrix = sun.get_model_matrix(time as f32);
-        let sun_uniforms = Uniforms {
-            model_matrix: sun_model_matrix,
-            view_matrix,
-            projection_matrix,
-            viewport_matrix,
-            time,
-            sun_position: sun_world_position,
+        // Keyboard camera controls would fight the path during playback
+        if !path_controller.is_playing() {
+            if let Some(index) = handle_input(&window, &mut camera, &bodies, render_time, delta_time) {
+                target = Some(index);
+            }
+        }
+        handle_path_input(&window, &camera, &mut path_controller);
+        handle_bookmark_input(&window, &mut camera, &mut bookmarks, args.bookmark_name.as_deref(), &bodies, &mut clock.tick);
+        // Mouse camera controls would fight the path too; the cursor is forgotten meanwhile
+        // so the first drag after playback doesn't jump
+        let click = if path_controller.is_playing() {
+            mouse = MouseState::new();
+            None
+        } else {
+            mouse.handle(&window, &mut camera).map(|position| display.to_framebuffer(&framebuffer, position))
+        };
+        camera.update(delta_time);
+        path_controller.update(&mut camera, delta_time);
+
+        // A new window size or internal resolution needs a new framebuffer.
+        // Picking can't use the old ID buffer then, so the click is dropped.
+        let (window_width, window_height) = window.get_size();
+        let old_scale = display.scale;
+        if window.is_key_pressed(Key::PageUp, minifb::KeyRepeat::No) {
+            display.step_scale(true);
+        }
+        if window.is_key_pressed(Key::PageDown, minifb::KeyRepeat::No) {
+            display.step_scale(false);
+        }
+        if window.is_key_pressed(Key::End, minifb::KeyRepeat::No) {
+            display.filter = display.filter.next();
+            println!("Scaling filter: {}", display.filter.name());
+        }
+        let resized = window_width > 0 && window_height > 0
+            && (window_width != display.window_width || window_height != display.window_height);
+        let click = if resized || display.scale != old_scale {
+            if resized {
+                display.resize(window_width, window_height);
+            }
+            framebuffer = window_framebuffer(&display, &background, framebuffer.antialiasing());
+            let (width, height) = display.framebuffer_size();
+            println!("Rendering at {}x{} ({}x)", width, height, display.scale);
+            None
+        } else {
+            click
         };
-        sun.render(&mut framebuffer, &sun_uniforms);
-       
-        framebuffer.set_current_color(0xFFDDDD);
 
+        if window.is_key_pressed(Key::Backslash, minifb::KeyRepeat::No) {
+            framebuffer.set_antialiasing(framebuffer.antialiasing().next());
+            println!("Antialiasing: {}", framebuffer.antialiasing().name());
+        }
+
+        if window.is_key_pressed(Key::Slash, minifb::KeyRepeat::No) {
+            options.tone_mapping.operator = options.tone_mapping.operator.next();
+            println!("Tone mapping: {}", options.tone_mapping.operator.name());
+        }
+        if window.is_key_pressed(Key::Key8, minifb::KeyRepeat::No) {
+            options.bloom.enabled = !options.bloom.enabled;
+        }
+        for (key, brighter) in [(Key::Key9, false), (Key::Key0, true)] {
+            if window.is_key_pressed(key, minifb::KeyRepeat::Yes) {
+                options.tone_mapping.brighten(brighter);
+                println!("Exposure: {:+.1} EV", options.tone_mapping.exposure);
+            }
+        }
+
+        if window.is_key_pressed(Key::Tab, minifb::KeyRepeat::No) {
+            layout = layout.next();
+        }
+        if window.is_key_pressed(Key::C, minifb::KeyRepeat::No) {
+            compare_index = (compare_index + 1) % bodies.len();
+        }
+        handle_stereo_input(&window, &mut stereo_settings, delta_time);
+        if window.is_key_pressed(Key::L, minifb::KeyRepeat::No) {
+            options.sky_overlay = options.sky_overlay.next();
+            println!("Sky overlay: {}", options.sky_overlay.name());
+        }
+        if window.is_key_pressed(Key::H, minifb::KeyRepeat::No) {
+            hud.visible = !hud.visible;
+        }
+        if window.is_key_pressed(Key::Z, minifb::KeyRepeat::No) {
+            show_info = !show_info;
+        }
+        if window.is_key_pressed(Key::Y, minifb::KeyRepeat::No) {
+            options.labels = !options.labels;
+        }
+        if window.is_key_pressed(Key::R, minifb::KeyRepeat::No) {
+            options.orbits = !options.orbits;
+        }
+        if window.is_key_pressed(Key::T, minifb::KeyRepeat::No) {
+            options.trails = !options.trails;
+        }
+        if window.is_key_pressed(Key::U, minifb::KeyRepeat::No) {
+            options.antialiased_lines = !options.antialiased_lines;
+        }
+        let context = SceneContext { options, ..context };
+
+        if window.is_key_pressed(Key::F10, minifb::KeyRepeat::No) {
+            let filename = format!("panorama_{}.png", timestamp());
+            if let Err(e) = export_panorama(&filename, &camera, &context, render_time) {
+                eprintln!("Panorama export failed: {}", e);
+            }
+        }
+
+        if window.is_key_pressed(Key::F11, minifb::KeyRepeat::No) {
+            let filename = format!("poster_{}.png", timestamp());
+            if let Err(e) = export_poster(&filename, &camera, args.poster_size, &context, render_time) {
+                eprintln!("Poster export failed: {}", e);
+            }
+        }
+
+        // The comparison view tracks its body as it orbits
+        let compare_target = bodies[compare_index].get_position(render_time);
+        compare_camera.set_view(compare_target + Vec3::new(0.0, 2.0, 10.0), compare_target, Vec3::new(0.0, 1.0, 0.0));
+
+        let viewports = layout.viewports(framebuffer.width, framebuffer.height, background.clear_color());
+        let views: Vec<(&Camera, Viewport)> = viewports
+            .iter()
+            .enumerate()
+            .map(|(index, viewport)| {
+                let view_camera = match (index, layout) {
+                    (0, _) => &camera,
+                    (_, Layout::SplitScreen) => &compare_camera,
+                    _ => &minimap_camera,
+                };
+                (view_camera, *viewport)
+            })
+            .collect();
+
+        // Picking reads the previous frame's ID buffer, so it runs before clearing.
+        // Insets are drawn last, so search the views from the top down.
+        let mut picked = None;
+        if let Some((x, y)) = click {
+            if let Some((view_camera, viewport)) = views.iter().rev().find(|(_, viewport)| viewport.contains(x, y)) {
+                let view_matrix = create_view_matrix(view_camera.eye, view_camera.center, view_camera.up);
+                let projection_matrix = view_camera.projection.matrix(viewport.width as f32, viewport.height as f32);
+                let hit = picking::unproject(x, y, &view_matrix, &projection_matrix, &viewport.matrix())
+                    .and_then(|ray| picking::pick(&framebuffer, x, y, &ray, &bodies, render_time));
+                picked = hit.map(|hit| {
+                    println!("Picked {} at ({:.2}, {:.2}, {:.2})", bodies[hit.index].name, hit.point.x, hit.point.y, hit.point.z);
+                    hit.index
+                });
+            }
+        }
+
+        for (index, (view_camera, viewport)) in views.iter().enumerate() {
+            // Only the main view is rendered in stereo
+            let settings = if index == 0 { &stereo_settings } else { &mono_settings };
+            stereo::render(&mut framebuffer, &mut eye_buffer, view_camera, viewport, settings, |framebuffer, view_matrix, projection_matrix, target| {
+                render_scene(framebuffer, &context, &ViewMatrices::new(view_matrix, projection_matrix, target.matrix()), render_time);
+            });
+        }
+        framebuffer.set_scissor(None);
+        // Everything after this is an overlay on the tone-mapped image, painted in order:
+        // labels, HUD, info panel and the REC marker
+        framebuffer.resolve(&options.tone_mapping, &options.bloom);
+
+        // Labels only go on the main view, once, whatever the stereo mode
+        if options.labels {
+            let (view_camera, viewport) = &views[0];
+            let view_matrix = create_view_matrix(view_camera.eye, view_camera.center, view_camera.up);
+            let projection_matrix = view_camera.projection.matrix(viewport.width as f32, viewport.height as f32);
+            framebuffer.set_scissor(Some(*viewport));
+            labels::render_labels(&mut framebuffer, &bodies, &view_matrix, &projection_matrix, &viewport.matrix(), render_time);
+            framebuffer.set_scissor(None);
+        }
+
+        if let Some(index) = picked {
+            warp_to_body(&mut camera, &bodies, index, render_time);
+            target = Some(index);
+        }
+
+        hud.update(delta_time);
+        hud.render(&mut framebuffer, &HudState {
+            camera: &camera,
+            bodies: &bodies,
+            target,
+            time: render_time,
+            days_per_tick,
+            days_per_second: days_per_tick / SIMULATION_STEP * args.time_scale,
+        });
+        if show_info {
+            info_panel::render(&mut framebuffer, target.map(|index| bodies[index]));
+        }
+
+        if window.is_key_pressed(Key::Home, minifb::KeyRepeat::No) && recorder.is_none() {
+            record_format = record_format.next();
+            println!("Recording format: {}", record_format.name());
+        }
+        if window.is_key_pressed(Key::Insert, minifb::KeyRepeat::No) {
+            match recorder.take() {
+                Some(finished) => {
+                    if let Err(e) = finished.finish() {
+                        eprintln!("Could not finish recording: {}", e);
+                    }
+                }
+                None => {
+                    let path = record_format.path_for(&format!("recording_{}", timestamp()));
+                    match Recorder::start(&path, record_format, framebuffer.width, framebuffer.height, args.fps) {
+                        Ok(started) => {
+                            println!("Recording {} to {}", record_format.name(), path);
+                            recorder = Some(started);
+                        }
+                        Err(e) => eprintln!("Could not start recording: {}", e),
+                    }
+                }
+            }
+        }
+        if let Some(active) = recorder.as_mut() {
+            if let Err(e) = active.record_frame(&framebuffer) {
+                eprintln!("Recording stopped: {}", e);
+                recorder = None;
+            }
+        }
+
+        // Saved after the HUD so the screenshot matches the window
+        if window.is_key_pressed(Key::F12, minifb::KeyRepeat::No) {
+            let filename = format!("screenshot_{}.png", timestamp());
+            match framebuffer.save_png(&filename) {
+                Ok(()) => println!("Saved screenshot to {}", filename),
+                Err(e) => eprintln!("Could not save screenshot: {}", e),
+            }
+        }
+
+        // Drawn after the frame is recorded, so it only shows in the window
+        if recorder.is_some() {
+            let (x, y) = (framebuffer.width as i32 - 44, framebuffer.height as i32 - 24);
+            text::draw_text(&mut framebuffer, x, y, "REC", 0xFF3030, 2, None);
+        }
+
+        let (window_width, window_height) = (display.window_width, display.window_height);
         window
-            .update_with_buffer(&framebuffer.buffer, framebuffer_width, framebuffer_height)
+            .update_with_buffer(display.present(&framebuffer), window_width, window_height)
             .unwrap();
 
-        std::thread::sleep(frame_delay);
+        // Only sleep for what is left of the frame budget
+        if let Some(remaining) = frame_delay.checked_sub(frame_start.elapsed()) {
+            std::thread::sleep(remaining);
+        }
     }
 }
 
-fn handle_input(window: &Window, camera: &mut Camera, planets: &[CelestialBody]){
-    let movement_speed= 1.0;
-    let rotation_speed = PI/50.0;
-    let zoom_speed = 0.1;
-
-    if window.is_key_pressed(Key::Key1, minifb::KeyRepeat::No) {
-        // Earth
-        let planet = &planets[0];
-        camera.start_warp(
-            planet.get_world_position() + Vec3::new(0.0, 2.0, 10.0), // Offset for viewing
-            planet.get_world_position(),
-            2.0, // 2-second animation
-        );
-    }
-
-    if window.is_key_pressed(Key::Key2, minifb::KeyRepeat::No) {
-        // Marte
-        let planet = &planets[1];
-        camera.start_warp(
-            planet.get_world_position() + Vec3::new(0.0, 2.0, 10.0), // Offset for viewing
-            planet.get_world_position(),
-            2.0, // 2-second animation
-        );
-    }
-
-    if window.is_key_pressed(Key::Key3, minifb::KeyRepeat::No) {
-        // Jupiter
-        let planet = &planets[2];
-        camera.start_warp(
-            planet.get_world_position() + Vec3::new(0.0, 2.0, 10.0), // Offset for viewing
-            planet.get_world_position(),
-            2.0, // 2-second animation
-        );
-    }
-
-    if window.is_key_pressed(Key::Key4, minifb::KeyRepeat::No) {
-        // Venus
-        let planet = &planets[3];
-        camera.start_warp(
-            planet.get_world_position() + Vec3::new(0.0, 2.0, 10.0), // Offset for viewing
-            planet.get_world_position(),
-            2.0, // 2-second animation
-        );
-    }
-
-    if window.is_key_pressed(Key::Key5, minifb::KeyRepeat::No) {
-        // Mercurio
-        let planet = &planets[4];
-        camera.start_warp(
-            planet.get_world_position() + Vec3::new(0.0, 2.0, 10.0), // Offset for viewing
-            planet.get_world_position(),
-            2.0, // 2-second animation
-        );
+// Bounding spheres of every body at the given time
+fn body_obstacles(bodies: &[&CelestialBody], time: f32) -> Vec<Obstacle> {
+    bodies
+        .iter()
+        .map(|body| Obstacle {
+            center: body.get_position(time),
+            radius: body.bounding_radius(),
+        })
+        .collect()
+}
+
+// Distance from the sun to the far edge of the outermost body
+fn scene_radius(bodies: &[&CelestialBody]) -> f32 {
+    bodies.iter().map(|body| body.orbit_radius + body.bounding_radius()).fold(0.0, f32::max)
+}
+
+// Flies to a viewing position next to the body, arcing around everything in the way
+fn warp_to_body(camera: &mut Camera, bodies: &[&CelestialBody], index: usize, time: f32) {
+    let Some(body) = bodies.get(index) else {
+        return;
+    };
+    let target = body.get_position(time);
+    let obstacles = body_obstacles(bodies, time);
+
+    camera.start_warp_around(target + Vec3::new(0.0, 2.0, 10.0), target, &obstacles);
+}
+
+// Returns the body warped to, if a number key was pressed
+fn handle_input(window: &Window, camera: &mut Camera, bodies: &[&CelestialBody], time: f32, delta_time: f32) -> Option<usize> {
+    // Per-second rates, scaled by the frame time
+    let movement_speed= 60.0 * delta_time;
+    let rotation_speed = 1.2 * PI * delta_time;
+    let zoom_speed = 6.0 * delta_time;
+
+    let mut target = None;
+    // The first five planets, in scene file order
+    let body_keys = [Key::Key1, Key::Key2, Key::Key3, Key::Key4, Key::Key5];
+    // The keys only pick planets; the sun is last in `bodies`, so keys past the planets do nothing
+    let planet_count = bodies.len().saturating_sub(1);
+    for (index, key) in body_keys.iter().enumerate().take(planet_count) {
+        if window.is_key_pressed(*key, minifb::KeyRepeat::No) {
+            warp_to_body(camera, bodies, index, time);
+            target = Some(index);
+        }
+    }
+
+    if window.is_key_pressed(Key::G, minifb::KeyRepeat::No) {
+        camera.warp_settings.easing = camera.warp_settings.easing.next();
+        println!("Warp easing: {}", camera.warp_settings.easing.name());
+    }
+
+    let fov_speed = PI / 3.0 * delta_time;
+    if window.is_key_down(Key::Minus){
+        camera.projection.set_fov(camera.projection.fov - fov_speed);
+    }
+    if window.is_key_down(Key::Equal){
+        camera.projection.set_fov(camera.projection.fov + fov_speed);
+    }
+    if window.is_key_down(Key::LeftBracket){
+        camera.dolly_zoom(-fov_speed);
+    }
+    if window.is_key_down(Key::RightBracket){
+        camera.dolly_zoom(fov_speed);
+    }
+    if window.is_key_pressed(Key::V, minifb::KeyRepeat::No) {
+        camera.toggle_orthographic();
+    }
+
+    if window.is_key_pressed(Key::B, minifb::KeyRepeat::No) {
+        camera.switch_to_birds_eye(scene_radius(bodies));
+    }
+    if window.is_key_pressed(Key::N, minifb::KeyRepeat::No) {
+        camera.switch_to_normal();
+    }
+    if window.is_key_pressed(Key::F, minifb::KeyRepeat::No) {
+        camera.switch_to_flight();
+    }
+
+    if camera.is_flying() {
+        handle_flight_input(window, camera, delta_time);
+        return target;
     }
 
     if window.is_key_down(Key::Left){
@@ -329,10 +876,127 @@ fn handle_input(window: &Window, camera: &mut Camera, planets: &[CelestialBody])
         camera.zoom(-zoom_speed);
     }
 
-    if window.is_key_pressed(Key::B, minifb::KeyRepeat::No) {
-        camera.switch_to_birds_eye();
+    target
+}
+
+const BOOKMARKS_FILE: &str = "bookmarks.txt";
+
+fn handle_bookmark_input(window: &Window, camera: &mut Camera, bookmarks: &mut Bookmarks, name: Option<&str>, bodies: &[&CelestialBody], time: &mut u32) {
+    let recall_keys = [Key::F1, Key::F2, Key::F3, Key::F4, Key::F5, Key::F6, Key::F7, Key::F8, Key::F9];
+
+    if window.is_key_pressed(Key::M, minifb::KeyRepeat::No) {
+        let name = bookmarks.next_name(name);
+        bookmarks.add(Bookmark::from_camera(&name, camera, *time));
+        match bookmarks.save(BOOKMARKS_FILE) {
+            Ok(()) => println!("Saved '{}' to {}", name, BOOKMARKS_FILE),
+            Err(e) => eprintln!("Could not save bookmarks: {}", e),
+        }
     }
-    if window.is_key_pressed(Key::N, minifb::KeyRepeat::No) {
-        camera.switch_to_normal();
+    if window.is_key_pressed(Key::J, minifb::KeyRepeat::No) {
+        bookmarks.print();
+    }
+
+    for (index, key) in recall_keys.iter().enumerate() {
+        if window.is_key_pressed(*key, minifb::KeyRepeat::No) {
+            if let Some(bookmark) = bookmarks.entries.get(index) {
+                *time = bookmark.time;
+                bookmark.recall(camera, &body_obstacles(bodies, *time as f32));
+            }
+        }
+    }
+}
+
+fn handle_stereo_input(window: &Window, stereo_settings: &mut StereoSettings, delta_time: f32) {
+    if window.is_key_pressed(Key::X, minifb::KeyRepeat::No) {
+        stereo_settings.mode = stereo_settings.mode.next();
+        println!("Stereo: {}", stereo_settings.mode.name());
+    }
+    if window.is_key_down(Key::Comma) {
+        stereo_settings.adjust_separation(-0.5 * delta_time);
+    }
+    if window.is_key_down(Key::Period) {
+        stereo_settings.adjust_separation(0.5 * delta_time);
+    }
+    if window.is_key_down(Key::Semicolon) {
+        stereo_settings.adjust_convergence(-5.0 * delta_time);
+    }
+    if window.is_key_down(Key::Apostrophe) {
+        stereo_settings.adjust_convergence(5.0 * delta_time);
+    }
+}
+
+fn handle_path_input(window: &Window, camera: &Camera, path_controller: &mut PathController) {
+    let path_file = "camera_path.txt";
+
+    if window.is_key_pressed(Key::K, minifb::KeyRepeat::No) {
+        path_controller.add_keyframe(camera);
+    }
+    if window.is_key_pressed(Key::P, minifb::KeyRepeat::No) {
+        path_controller.toggle_playback();
+    }
+    if window.is_key_pressed(Key::O, minifb::KeyRepeat::No) {
+        match path_controller.path.save(path_file) {
+            Ok(()) => println!("Saved camera path to {}", path_file),
+            Err(e) => eprintln!("Could not save camera path: {}", e),
+        }
+    }
+    if window.is_key_pressed(Key::I, minifb::KeyRepeat::No) {
+        match CameraPath::load(path_file) {
+            Ok(path) => *path_controller = PathController::new(path),
+            Err(e) => eprintln!("Could not load camera path: {}", e),
+        }
     }
-}
\ No newline at end of file
+}
+
+fn handle_flight_input(window: &Window, camera: &mut Camera, delta_time: f32) {
+    let thrust = 6.0 * delta_time;
+    let turn_speed = 0.6 * PI * delta_time;
+
+    let mut local = Vec3::new(0.0, 0.0, 0.0);
+    if window.is_key_down(Key::W){
+        local.z -= thrust;
+    }
+    if window.is_key_down(Key::S){
+        local.z += thrust;
+    }
+    if window.is_key_down(Key::A){
+        local.x -= thrust;
+    }
+    if window.is_key_down(Key::D){
+        local.x += thrust;
+    }
+    if window.is_key_down(Key::Space){
+        local.y += thrust;
+    }
+    if window.is_key_down(Key::LeftShift){
+        local.y -= thrust;
+    }
+    if local.magnitude() > 0.0 {
+        camera.thrust(local);
+    }
+
+    let mut yaw = 0.0;
+    let mut pitch = 0.0;
+    let mut roll = 0.0;
+    if window.is_key_down(Key::Left){
+        yaw += turn_speed;
+    }
+    if window.is_key_down(Key::Right){
+        yaw -= turn_speed;
+    }
+    if window.is_key_down(Key::Up){
+        pitch += turn_speed;
+    }
+    if window.is_key_down(Key::Down){
+        pitch -= turn_speed;
+    }
+    if window.is_key_down(Key::Q){
+        roll += turn_speed;
+    }
+    if window.is_key_down(Key::E){
+        roll -= turn_speed;
+    }
+    if yaw != 0.0 || pitch != 0.0 || roll != 0.0 {
+        camera.rotate_local(yaw, pitch, roll);
+    }
+}
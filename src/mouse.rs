@@ -0,0 +1,121 @@
+use minifb::{MouseButton, MouseMode, Window};
+use nalgebra_glm::Vec3;
+use crate::camera::Camera;
+
+const ORBIT_SENSITIVITY: f32 = 0.01; // Radians per pixel dragged
+const PAN_SENSITIVITY: f32 = 0.1; // Scaled again by move_center
+const ZOOM_FACTOR: f32 = 0.9; // Distance multiplier per scroll step
+const CLICK_THRESHOLD: f32 = 4.0; // Pixels the cursor may move and still count as a click
+
+// Whether the cursor has moved far enough from where the button went down to be a drag
+fn is_drag(press: (f32, f32), position: (f32, f32)) -> bool {
+    (position.0 - press.0).hypot(position.1 - press.1) > CLICK_THRESHOLD
+}
+
+// Exponential zoom: every scroll step covers the same fraction of the distance.
+// Gives how far to move the eye towards the center.
+fn scroll_zoom(distance: f32, scroll: f32) -> f32 {
+    distance - distance * ZOOM_FACTOR.powf(scroll)
+}
+
+pub struct MouseState {
+    last_position: Option<(f32, f32)>,
+    press_position: Option<(f32, f32)>,
+    dragged: bool,
+    left_was_down: bool,
+    middle_was_down: bool,
+}
+
+impl MouseState {
+    pub fn new() -> Self {
+        MouseState {
+            last_position: None,
+            press_position: None,
+            dragged: false,
+            left_was_down: false,
+            middle_was_down: false,
+        }
+    }
+
+    // Applies left-drag orbit (or looking around in flight mode), middle-drag panning and scroll zoom to the camera.
+    // Returns the cursor position when the left button was clicked without dragging.
+    pub fn handle(&mut self, window: &Window, camera: &mut Camera) -> Option<(f32, f32)> {
+        let position = window.get_mouse_pos(MouseMode::Discard);
+        let left_down = window.get_mouse_down(MouseButton::Left);
+        let middle_down = window.get_mouse_down(MouseButton::Middle);
+        let mut click = None;
+
+        if let (Some((x, y)), Some((last_x, last_y))) = (position, self.last_position) {
+            let dx = x - last_x;
+            let dy = y - last_y;
+
+            if left_down && self.left_was_down {
+                if camera.is_flying() {
+                    camera.rotate_local(-dx * ORBIT_SENSITIVITY, -dy * ORBIT_SENSITIVITY, 0.0);
+                } else {
+                    camera.orbit(dx * ORBIT_SENSITIVITY, -dy * ORBIT_SENSITIVITY);
+                }
+            }
+            if middle_down && self.middle_was_down && (dx != 0.0 || dy != 0.0) {
+                camera.move_center(Vec3::new(-dx * PAN_SENSITIVITY, dy * PAN_SENSITIVITY, 0.0));
+            }
+        }
+
+        // Distinguish a click from the start of a drag
+        if left_down && !self.left_was_down {
+            self.press_position = position;
+            self.dragged = false;
+        }
+        if left_down {
+            if let (Some(position), Some(press)) = (position, self.press_position) {
+                if is_drag(press, position) {
+                    self.dragged = true;
+                }
+            }
+        }
+        if !left_down && self.left_was_down {
+            if !self.dragged {
+                click = self.press_position;
+            }
+            self.press_position = None;
+        }
+
+        if let Some((_, scroll)) = window.get_scroll_wheel() {
+            if scroll != 0.0 {
+                camera.zoom(scroll_zoom((camera.center - camera.eye).magnitude(), scroll));
+            }
+        }
+
+        self.last_position = position;
+        self.left_was_down = left_down;
+        self.middle_was_down = middle_down;
+
+        click
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_movements_still_count_as_clicks() {
+        assert!(!is_drag((100.0, 100.0), (100.0, 100.0)));
+        assert!(!is_drag((100.0, 100.0), (103.0, 102.0)));
+        assert!(is_drag((100.0, 100.0), (103.0, 103.0)));
+        assert!(is_drag((100.0, 100.0), (90.0, 100.0)));
+    }
+
+    #[test]
+    fn each_scroll_step_covers_the_same_fraction_of_the_distance() {
+        assert!((scroll_zoom(10.0, 1.0) - 1.0).abs() < 1e-5);
+        assert!((scroll_zoom(100.0, 1.0) - 10.0).abs() < 1e-4);
+        // Two steps at once land where two single steps would
+        let after_one = 10.0 - scroll_zoom(10.0, 1.0);
+        let after_two = after_one - scroll_zoom(after_one, 1.0);
+        assert!((10.0 - scroll_zoom(10.0, 2.0) - after_two).abs() < 1e-5);
+        // Scrolling back out undoes it
+        assert!((after_one - scroll_zoom(after_one, -1.0) - 10.0).abs() < 1e-4);
+        assert_eq!(scroll_zoom(10.0, 0.0), 0.0);
+    }
+}
@@ -0,0 +1,118 @@
+use nalgebra_glm::{Mat4, Vec3, Vec4};
+use std::f32::consts::PI;
+use crate::celestial_body::CelestialBody;
+use crate::framebuffer::Framebuffer;
+
+const ORBIT_SEGMENTS: usize = 128;
+const ORBIT_OPACITY: f32 = 0.35;
+const TRAIL_SEGMENTS: usize = 32;
+const TRAIL_ANGLE: f32 = PI / 3.0; // How much of the orbit the trail covers
+
+// Clips a world-space segment against the near plane, then maps both ends to the screen
+fn project_segment(view_projection: &Mat4, viewport_matrix: &Mat4, start: Vec3, end: Vec3) -> Option<(Vec3, Vec3)> {
+    let mut a = view_projection * Vec4::new(start.x, start.y, start.z, 1.0);
+    let mut b = view_projection * Vec4::new(end.x, end.y, end.z, 1.0);
+
+    // Signed distance to the near plane (z = -w in clip space)
+    let distance_a = a.z + a.w;
+    let distance_b = b.z + b.w;
+    if distance_a < 0.0 && distance_b < 0.0 {
+        return None;
+    }
+    if distance_a < 0.0 {
+        a = a + (b - a) * (distance_a / (distance_a - distance_b));
+    } else if distance_b < 0.0 {
+        b = a + (b - a) * (distance_a / (distance_a - distance_b));
+    }
+
+    let to_screen = |clip: Vec4| {
+        let w = clip.w.max(1e-6);
+        let screen = viewport_matrix * Vec4::new(clip.x / w, clip.y / w, clip.z / w, 1.0);
+        Vec3::new(screen.x, screen.y, screen.z)
+    };
+    Some((to_screen(a), to_screen(b)))
+}
+
+/// Draws the full orbit of the body as a faint closed polyline in its orbit color.
+pub fn render_orbit(framebuffer: &mut Framebuffer, body: &CelestialBody, view_projection: &Mat4, viewport_matrix: &Mat4, antialiased: bool) {
+    if body.orbit_radius == 0.0 || body.orbit_speed == 0.0 {
+        return;
+    }
+
+    let period = 2.0 * PI / body.orbit_speed.abs();
+    let positions: Vec<Vec3> = (0..=ORBIT_SEGMENTS)
+        .map(|i| body.get_position(period * i as f32 / ORBIT_SEGMENTS as f32))
+        .collect();
+
+    framebuffer.set_current_color(body.orbit_color);
+
+    // Consecutive visible segments are drawn as one polyline; clipping breaks it up
+    let mut run: Vec<Vec3> = Vec::new();
+    for pair in positions.windows(2) {
+        match project_segment(view_projection, viewport_matrix, pair[0], pair[1]) {
+            Some((start, end)) => {
+                if run.last() != Some(&start) {
+                    framebuffer.polyline(&run, ORBIT_OPACITY, antialiased);
+                    run.clear();
+                    run.push(start);
+                }
+                run.push(end);
+            }
+            None => {
+                framebuffer.polyline(&run, ORBIT_OPACITY, antialiased);
+                run.clear();
+            }
+        }
+    }
+    framebuffer.polyline(&run, ORBIT_OPACITY, antialiased);
+}
+
+/// Draws the stretch of orbit the body just travelled, fading out towards the tail.
+pub fn render_trail(framebuffer: &mut Framebuffer, body: &CelestialBody, view_projection: &Mat4, viewport_matrix: &Mat4, time: f32, antialiased: bool) {
+    if body.orbit_radius == 0.0 || body.orbit_speed == 0.0 {
+        return;
+    }
+
+    let length = TRAIL_ANGLE / body.orbit_speed.abs();
+    let positions: Vec<Vec3> = (0..=TRAIL_SEGMENTS)
+        .map(|i| body.get_position(time - length * i as f32 / TRAIL_SEGMENTS as f32))
+        .collect();
+
+    framebuffer.set_current_color(body.orbit_color);
+    for (i, pair) in positions.windows(2).enumerate() {
+        if let Some((start, end)) = project_segment(view_projection, viewport_matrix, pair[0], pair[1]) {
+            let opacity = 1.0 - i as f32 / TRAIL_SEGMENTS as f32;
+            framebuffer.line_3d(start, end, opacity, antialiased);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Camera at z = 10 looking down -z, into an 800x600 viewport
+    fn matrices() -> (Mat4, Mat4) {
+        let view = crate::create_view_matrix(Vec3::new(0.0, 0.0, 10.0), Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let projection = crate::create_perspective_matrix(800.0, 600.0, 1.0);
+        (projection * view, crate::create_viewport_matrix(800.0, 600.0))
+    }
+
+    #[test]
+    fn segments_behind_the_camera_are_dropped() {
+        let (view_projection, viewport) = matrices();
+        assert!(project_segment(&view_projection, &viewport, Vec3::new(-1.0, 0.0, 12.0), Vec3::new(1.0, 0.0, 20.0)).is_none());
+    }
+
+    #[test]
+    fn segments_through_the_near_plane_are_cut_at_it() {
+        let (view_projection, viewport) = matrices();
+        let (start, end) = project_segment(&view_projection, &viewport, Vec3::new(1.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 30.0)).unwrap();
+        // The far end is where the point in front projects to
+        let (front, _) = project_segment(&view_projection, &viewport, Vec3::new(1.0, 0.0, 0.0), Vec3::new(1.0, 0.0, -1.0)).unwrap();
+        assert!((start - front).magnitude() < 1e-3);
+        // The cut end sits on the near plane, at finite screen coordinates
+        assert!(end.x.is_finite() && end.y.is_finite());
+        assert!((end.z + 1.0).abs() < 1e-3, "{}", end.z);
+    }
+}
@@ -0,0 +1,194 @@
+use nalgebra_glm::{Mat4, Vec3};
+use std::f32::consts::PI;
+use crate::camera::Camera;
+use crate::framebuffer::Framebuffer;
+use crate::viewport::Viewport;
+
+const FACE_SIZE: usize = 512;
+const PANORAMA_WIDTH: usize = 2048;
+const PANORAMA_HEIGHT: usize = 1024;
+
+pub struct CubeFace {
+    pub name: &'static str,
+    pub forward: Vec3,
+    pub up: Vec3,
+    pub framebuffer: Framebuffer,
+}
+
+// Forward and up vectors of the six 90° views
+fn face_directions() -> [(&'static str, Vec3, Vec3); 6] {
+    [
+        ("px", Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0)),
+        ("nx", Vec3::new(-1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0)),
+        ("py", Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 1.0)),
+        ("ny", Vec3::new(0.0, -1.0, 0.0), Vec3::new(0.0, 0.0, -1.0)),
+        ("pz", Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 1.0, 0.0)),
+        ("nz", Vec3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 1.0, 0.0)),
+    ]
+}
+
+// Faces with blank framebuffers, in the order they are named on disk
+pub fn empty_faces(size: usize) -> Vec<CubeFace> {
+    face_directions()
+        .into_iter()
+        .map(|(name, forward, up)| CubeFace { name, forward, up, framebuffer: Framebuffer::new(size, size) })
+        .collect()
+}
+
+/// Renders the six cube faces around `eye`. `draw` renders the scene with the given matrices.
+pub fn render_cubemap<F>(eye: Vec3, size: usize, background_color: u32, mut draw: F) -> Vec<CubeFace>
+where
+    F: FnMut(&mut Framebuffer, Mat4, Mat4, &Viewport),
+{
+    let viewport = Viewport::new(0, 0, size, size, background_color);
+    let projection_matrix = super::create_perspective_matrix(size as f32, size as f32, PI / 2.0);
+
+    face_directions()
+        .into_iter()
+        .map(|(name, forward, up)| {
+            let mut framebuffer = Framebuffer::new(size, size);
+            framebuffer.clear_viewport(&viewport);
+
+            let view_matrix = super::create_view_matrix(eye, eye + forward, up);
+            draw(&mut framebuffer, view_matrix, projection_matrix, &viewport);
+
+            CubeFace { name, forward, up, framebuffer }
+        })
+        .collect()
+}
+
+// Nearest-sample lookup of a world direction in the cubemap
+pub fn sample_cubemap(faces: &[CubeFace], direction: Vec3) -> u32 {
+    // The face looking most directly along the direction contains it
+    let face = faces
+        .iter()
+        .max_by(|a, b| direction.dot(&a.forward).total_cmp(&direction.dot(&b.forward)))
+        .unwrap();
+
+    let right = face.forward.cross(&face.up);
+    let depth = direction.dot(&face.forward);
+    let ndc_x = direction.dot(&right) / depth;
+    let ndc_y = direction.dot(&face.up) / depth;
+
+    let size = face.framebuffer.width;
+    let x = (((ndc_x + 1.0) / 2.0 * size as f32) as usize).min(size - 1);
+    let y = (((1.0 - ndc_y) / 2.0 * size as f32) as usize).min(size - 1);
+
+    face.framebuffer.buffer[y * size + x]
+}
+
+/// Resamples the cubemap to an equirectangular image whose center looks along `forward`,
+/// levelled to the horizon.
+pub fn equirectangular(faces: &[CubeFace], forward: Vec3, width: usize, height: usize) -> Framebuffer {
+    let heading = Vec3::new(forward.x, 0.0, forward.z)
+        .try_normalize(1.0e-6)
+        .unwrap_or(Vec3::new(0.0, 0.0, -1.0));
+    let east = heading.cross(&Vec3::new(0.0, 1.0, 0.0));
+
+    let mut panorama = Framebuffer::new(width, height);
+    for y in 0..height {
+        let latitude = PI / 2.0 - (y as f32 + 0.5) / height as f32 * PI;
+        for x in 0..width {
+            let longitude = (x as f32 + 0.5) / width as f32 * 2.0 * PI - PI;
+            let direction = heading * (latitude.cos() * longitude.cos())
+                + east * (latitude.cos() * longitude.sin())
+                + Vec3::new(0.0, latitude.sin(), 0.0);
+
+            panorama.buffer[y * width + x] = sample_cubemap(faces, direction);
+        }
+    }
+
+    panorama
+}
+
+/// Writes `<stem>.png` as an equirectangular panorama from the camera eye,
+/// plus the six cube faces as `<stem>_<face>.png`.
+pub fn export<F>(filename: &str, camera: &Camera, background_color: u32, draw: F) -> image::ImageResult<()>
+where
+    F: FnMut(&mut Framebuffer, Mat4, Mat4, &Viewport),
+{
+    let stem = filename.strip_suffix(".png").unwrap_or(filename);
+    let faces = render_cubemap(camera.eye, FACE_SIZE, background_color, draw);
+
+    for face in &faces {
+        face.framebuffer.save_png(&format!("{}_{}.png", stem, face.name))?;
+    }
+
+    let forward = camera.center - camera.eye;
+    equirectangular(&faces, forward, PANORAMA_WIDTH, PANORAMA_HEIGHT).save_png(&format!("{}.png", stem))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra_glm::Vec4;
+
+    // Faces filled with their own index, so a sample tells which face it came from
+    fn numbered_faces(size: usize) -> Vec<CubeFace> {
+        let mut faces = empty_faces(size);
+        for (index, face) in faces.iter_mut().enumerate() {
+            face.framebuffer.buffer.fill(index as u32);
+        }
+        faces
+    }
+
+    #[test]
+    fn axes_hit_the_centers_of_their_faces() {
+        let size = 9;
+        let mut faces = empty_faces(size);
+        for (index, face) in faces.iter_mut().enumerate() {
+            for (pixel, value) in face.framebuffer.buffer.iter_mut().enumerate() {
+                *value = ((index as u32) << 16) | pixel as u32;
+            }
+        }
+
+        let center = (size / 2 * size + size / 2) as u32;
+        for (index, (name, forward, _)) in face_directions().into_iter().enumerate() {
+            assert_eq!(sample_cubemap(&faces, forward), ((index as u32) << 16) | center, "{}", name);
+        }
+    }
+
+    #[test]
+    fn samples_land_where_the_faces_were_drawn() {
+        let directions = [
+            Vec3::new(1.0, 0.3, -0.4),
+            Vec3::new(-0.2, 0.9, 0.5),
+            Vec3::new(0.1, -1.0, -0.6),
+            Vec3::new(0.7, -0.2, 1.0),
+            Vec3::new(-0.5, 0.4, -1.0),
+            Vec3::new(-1.0, -0.6, 0.2),
+        ];
+        // Mark each direction where the face's own camera sees it
+        let faces = render_cubemap(Vec3::zeros(), 64, 0, |framebuffer, view_matrix, projection_matrix, viewport| {
+            for (marker, direction) in directions.iter().enumerate() {
+                let clip = projection_matrix * view_matrix * Vec4::new(direction.x, direction.y, direction.z, 1.0);
+                if clip.w <= 0.0 || clip.x.abs() > clip.w || clip.y.abs() > clip.w {
+                    continue;
+                }
+                let screen = viewport.matrix() * (clip / clip.w);
+                let (x, y) = ((screen.x as usize).min(63), (screen.y as usize).min(63));
+                framebuffer.buffer[y * 64 + x] = marker as u32 + 1;
+            }
+        });
+
+        for (marker, direction) in directions.iter().enumerate() {
+            assert_eq!(sample_cubemap(&faces, *direction), marker as u32 + 1, "{:?}", direction);
+        }
+    }
+
+    #[test]
+    fn panorama_wraps_around_the_forward_view() {
+        let faces = numbered_faces(4);
+        let (width, height) = (16, 8);
+        let panorama = equirectangular(&faces, Vec3::new(0.0, -0.5, -1.0), width, height);
+        let face_at = |x: usize, y: usize| face_directions()[panorama.buffer[y * width + x] as usize].0;
+
+        // Levelled, so the center looks straight along -z
+        assert_eq!(face_at(width / 2, height / 2), "nz");
+        assert_eq!(face_at(width * 3 / 4, height / 2), "px");
+        assert_eq!(face_at(width / 4, height / 2), "nx");
+        assert_eq!(face_at(0, height / 2), "pz");
+        assert_eq!(face_at(width / 2, 0), "py");
+        assert_eq!(face_at(width / 2, height - 1), "ny");
+    }
+}
@@ -0,0 +1,175 @@
+use nalgebra_glm::{Vec3, Vec4, Mat4};
+use crate::celestial_body::CelestialBody;
+use crate::framebuffer::Framebuffer;
+
+pub struct Ray {
+    pub origin: Vec3,
+    pub direction: Vec3,
+}
+
+pub struct PickHit {
+    pub index: usize,
+    pub point: Vec3,
+    pub distance: f32,
+}
+
+impl Ray {
+    pub fn at(&self, distance: f32) -> Vec3 {
+        self.origin + self.direction * distance
+    }
+}
+
+/// Turns a framebuffer coordinate into a world-space ray going from the near plane to the far plane.
+pub fn unproject(x: f32, y: f32, view_matrix: &Mat4, projection_matrix: &Mat4, viewport_matrix: &Mat4) -> Option<Ray> {
+    let ndc = viewport_matrix.try_inverse()? * Vec4::new(x, y, 0.0, 1.0);
+    let inverse_view_projection = (projection_matrix * view_matrix).try_inverse()?;
+
+    let near = inverse_view_projection * Vec4::new(ndc.x, ndc.y, -1.0, 1.0);
+    let far = inverse_view_projection * Vec4::new(ndc.x, ndc.y, 1.0, 1.0);
+    let near = near.xyz() / near.w;
+    let far = far.xyz() / far.w;
+
+    Some(Ray {
+        origin: near,
+        direction: (far - near).normalize(),
+    })
+}
+
+/// Distance along the ray to the first intersection with the sphere, if any.
+pub fn intersect_sphere(ray: &Ray, center: Vec3, radius: f32) -> Option<f32> {
+    let offset = ray.origin - center;
+    let b = offset.dot(&ray.direction);
+    let c = offset.magnitude_squared() - radius * radius;
+    let discriminant = b * b - c;
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    let root = discriminant.sqrt();
+    let near = -b - root;
+    let far = -b + root;
+    if near >= 0.0 {
+        Some(near)
+    } else if far >= 0.0 {
+        Some(far) // Ray starts inside the sphere
+    } else {
+        None
+    }
+}
+
+/// Closest body whose bounding sphere is hit by the ray.
+pub fn pick_body(ray: &Ray, bodies: &[&CelestialBody], time: f32) -> Option<PickHit> {
+    let mut closest: Option<PickHit> = None;
+
+    for (index, body) in bodies.iter().enumerate() {
+        let center = body.get_position(time);
+        if let Some(distance) = intersect_sphere(ray, center, body.bounding_radius()) {
+            if closest.as_ref().is_none_or(|hit| distance < hit.distance) {
+                closest = Some(PickHit { index, point: ray.at(distance), distance });
+            }
+        }
+    }
+
+    closest
+}
+
+/// Uses the framebuffer's ID buffer for pixel-exact picking when it is enabled,
+/// falling back to bounding spheres otherwise.
+pub fn pick(framebuffer: &Framebuffer, x: f32, y: f32, ray: &Ray, bodies: &[&CelestialBody], time: f32) -> Option<PickHit> {
+    if !framebuffer.has_id_buffer() {
+        return pick_body(ray, bodies, time);
+    }
+
+    let index = framebuffer.id_at(x as usize, y as usize)?;
+    let body = bodies.get(index)?;
+    let center = body.get_position(time);
+
+    // The mesh is slightly smaller than its bounding sphere, so silhouette pixels may miss it
+    let distance = intersect_sphere(ray, center, body.bounding_radius())
+        .unwrap_or_else(|| (center - ray.origin).dot(&ray.direction));
+
+    Some(PickHit { index, point: ray.at(distance), distance })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::celestial_body::still_body;
+    use crate::{create_perspective_matrix, create_view_matrix, create_viewport_matrix};
+    use std::f32::consts::PI;
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).magnitude() < 1e-3, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn center_of_the_screen_looks_along_the_view_direction() {
+        let eye = Vec3::new(0.0, 0.0, 10.0);
+        let view = create_view_matrix(eye, Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let projection = create_perspective_matrix(800.0, 600.0, PI / 3.0);
+        let viewport = create_viewport_matrix(800.0, 600.0);
+
+        let ray = unproject(400.0, 300.0, &view, &projection, &viewport).unwrap();
+        assert_close(ray.direction, Vec3::new(0.0, 0.0, -1.0));
+        // Starts on the near plane, in front of the eye
+        assert!((ray.origin.z - (eye.z - 0.1)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn unprojected_ray_passes_through_the_projected_point() {
+        let view = create_view_matrix(Vec3::new(3.0, 4.0, 12.0), Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let projection = create_perspective_matrix(640.0, 480.0, PI / 4.0);
+        let viewport = create_viewport_matrix(640.0, 480.0);
+
+        let point = Vec3::new(2.0, -1.0, 0.5);
+        let clip = projection * view * Vec4::new(point.x, point.y, point.z, 1.0);
+        let screen = viewport * Vec4::new(clip.x / clip.w, clip.y / clip.w, clip.z / clip.w, 1.0);
+
+        let ray = unproject(screen.x, screen.y, &view, &projection, &viewport).unwrap();
+        let along = (point - ray.origin).dot(&ray.direction);
+        assert_close(ray.origin + ray.direction * along, point);
+    }
+
+    #[test]
+    fn sphere_in_front_is_hit_at_its_near_side() {
+        let ray = Ray { origin: Vec3::new(0.0, 0.0, 10.0), direction: Vec3::new(0.0, 0.0, -1.0) };
+        let distance = intersect_sphere(&ray, Vec3::new(0.0, 0.0, 0.0), 2.0).unwrap();
+        assert!((distance - 8.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn ray_inside_sphere_hits_the_far_side() {
+        let ray = Ray { origin: Vec3::new(0.0, 0.0, 0.0), direction: Vec3::new(1.0, 0.0, 0.0) };
+        let distance = intersect_sphere(&ray, Vec3::new(0.0, 0.0, 0.0), 3.0).unwrap();
+        assert!((distance - 3.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn sphere_behind_or_beside_the_ray_is_missed() {
+        let ray = Ray { origin: Vec3::new(0.0, 0.0, 10.0), direction: Vec3::new(0.0, 0.0, 1.0) };
+        assert!(intersect_sphere(&ray, Vec3::new(0.0, 0.0, 0.0), 2.0).is_none());
+
+        let ray = Ray { origin: Vec3::new(5.0, 0.0, 10.0), direction: Vec3::new(0.0, 0.0, -1.0) };
+        assert!(intersect_sphere(&ray, Vec3::new(0.0, 0.0, 0.0), 2.0).is_none());
+    }
+
+    #[test]
+    fn id_buffer_and_sphere_picks_agree_on_the_hit_point() {
+        let vertex = crate::vertex::Vertex::new(Vec3::new(2.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), nalgebra_glm::Vec2::zeros());
+        let body = still_body("Sphere", vec![vertex]);
+        let ray = Ray { origin: Vec3::new(0.0, 0.0, 10.0), direction: Vec3::new(0.0, 0.0, -1.0) };
+
+        let hit = pick_body(&ray, &[&body], 0.0).unwrap();
+        assert_eq!(hit.index, 0);
+        assert_close(hit.point, Vec3::new(0.0, 0.0, 2.0));
+
+        let mut framebuffer = Framebuffer::new(4, 4);
+        framebuffer.enable_id_buffer();
+        framebuffer.set_current_id(Some(0));
+        framebuffer.point(1, 1, 0.5);
+        let hit = pick(&framebuffer, 1.0, 1.0, &ray, &[&body], 0.0).unwrap();
+        assert_eq!(hit.index, 0);
+        assert_close(hit.point, Vec3::new(0.0, 0.0, 2.0));
+        assert!((hit.distance - 8.0).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,194 @@
+use nalgebra_glm::Mat4;
+use crate::background;
+use crate::camera::Camera;
+use crate::framebuffer::Framebuffer;
+use crate::ViewMatrices;
+
+pub const DEFAULT_SIZE: (usize, usize) = (7680, 4320);
+const TILE_SIZE: usize = 512;
+
+/// How the poster is cut up: square tiles, each drawn with `margin` extra pixels on
+/// every side so light spread across tiles (bloom) has no seams.
+#[derive(Clone, Copy)]
+pub struct Tiling {
+    pub tile_size: usize,
+    pub margin: usize,
+}
+
+impl Tiling {
+    // Tiles grow with the margin, so the margins stay a small part of what gets drawn
+    pub fn for_margin(margin: usize) -> Self {
+        Tiling { tile_size: TILE_SIZE.max(4 * margin), margin }
+    }
+}
+
+// "7680x4320" -> (7680, 4320)
+pub fn parse_size(text: &str) -> Option<(usize, usize)> {
+    let (width, height) = text.split_once('x')?;
+    let size = (width.trim().parse().ok()?, height.trim().parse().ok()?);
+    if size.0 == 0 || size.1 == 0 {
+        None
+    } else {
+        Some(size)
+    }
+}
+
+/// Narrows a projection to the pixels [x0, x1) x [y0, y1) of a width x height image.
+/// It scales and shifts clip space after projecting, so it works for any projection.
+pub fn tile_projection(projection_matrix: &Mat4, width: usize, height: usize, x0: usize, y0: usize, x1: usize, y1: usize) -> Mat4 {
+    let scale_x = width as f32 / (x1 - x0) as f32;
+    let scale_y = height as f32 / (y1 - y0) as f32;
+    // Center of the tile in NDC; y points up in NDC but down in the image
+    let center_x = (x0 + x1) as f32 / width as f32 - 1.0;
+    let center_y = 1.0 - (y0 + y1) as f32 / height as f32;
+
+    let crop = Mat4::new(
+        scale_x, 0.0, 0.0, -center_x * scale_x,
+        0.0, scale_y, 0.0, -center_y * scale_y,
+        0.0, 0.0, 1.0, 0.0,
+        0.0, 0.0, 0.0, 1.0,
+    );
+    crop * projection_matrix
+}
+
+/// Renders the camera view at any size by drawing it one tile at a time into a small
+/// framebuffer, then copying each tile into the final image.
+pub fn render<F>(camera: &Camera, width: usize, height: usize, tiling: Tiling, background_color: u32, mut draw: F) -> image::RgbImage
+where
+    F: FnMut(&mut Framebuffer, &ViewMatrices),
+{
+    let view_matrix = super::create_view_matrix(camera.eye, camera.center, camera.up);
+    let projection_matrix = camera.projection.matrix(width as f32, height as f32);
+    // The sky of the whole image, cut up like the scene so the tiles line up
+    let sky_projection_matrix = background::sky_projection(&projection_matrix, width as f32, height as f32);
+    let mut image = image::RgbImage::new(width as u32, height as u32);
+    let Tiling { tile_size, margin } = tiling;
+
+    for y0 in (0..height).step_by(tile_size) {
+        for x0 in (0..width).step_by(tile_size) {
+            let x1 = (x0 + tile_size).min(width);
+            let y1 = (y0 + tile_size).min(height);
+            // The tile with its margin, cut off at the edges of the image
+            let (left, top) = (x0.saturating_sub(margin), y0.saturating_sub(margin));
+            let (right, bottom) = ((x1 + margin).min(width), (y1 + margin).min(height));
+
+            let mut framebuffer = Framebuffer::new(right - left, bottom - top);
+            framebuffer.set_background_color(background_color);
+            framebuffer.clear();
+            let matrices = ViewMatrices {
+                view_matrix,
+                projection_matrix: tile_projection(&projection_matrix, width, height, left, top, right, bottom),
+                sky_projection_matrix: tile_projection(&sky_projection_matrix, width, height, left, top, right, bottom),
+                viewport_matrix: super::create_viewport_matrix((right - left) as f32, (bottom - top) as f32),
+            };
+            draw(&mut framebuffer, &matrices);
+
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    let color = framebuffer.buffer[(y - top) * framebuffer.width + (x - left)];
+                    image.put_pixel(x as u32, y as u32, image::Rgb([(color >> 16) as u8, (color >> 8) as u8, color as u8]));
+                }
+            }
+        }
+    }
+
+    image
+}
+
+pub fn export<F>(filename: &str, camera: &Camera, width: usize, height: usize, margin: usize, background_color: u32, draw: F) -> image::ImageResult<()>
+where
+    F: FnMut(&mut Framebuffer, &ViewMatrices),
+{
+    render(camera, width, height, Tiling::for_margin(margin), background_color, draw).save(filename)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra_glm::Vec4;
+
+    // Image pixel that a clip-space point lands on, for an image of the given size
+    fn to_pixel(clip: Vec4, width: f32, height: f32) -> (f32, f32) {
+        let (x, y) = (clip.x / clip.w, clip.y / clip.w);
+        ((x + 1.0) / 2.0 * width, (1.0 - y) / 2.0 * height)
+    }
+
+    #[test]
+    fn whole_image_tile_leaves_the_projection_alone() {
+        let projection = crate::create_perspective_matrix(800.0, 600.0, 1.0);
+        let tile = tile_projection(&projection, 800, 600, 0, 0, 800, 600);
+        assert!((tile - projection).abs().max() < 1e-6);
+    }
+
+    #[test]
+    fn tile_shows_its_part_of_the_image() {
+        let (width, height) = (1000, 600);
+        for projection in [
+            crate::create_perspective_matrix(width as f32, height as f32, 1.0),
+            crate::create_orthographic_matrix(width as f32, height as f32, 18.0),
+        ] {
+            let tile = tile_projection(&projection, width, height, 512, 0, 1000, 512);
+            for point in [Vec4::new(1.0, 2.0, -10.0, 1.0), Vec4::new(-3.0, -1.0, -25.0, 1.0), Vec4::new(4.0, 0.5, -8.0, 1.0)] {
+                let (image_x, image_y) = to_pixel(projection * point, width as f32, height as f32);
+                let (tile_x, tile_y) = to_pixel(tile * point, 488.0, 512.0);
+                assert!((tile_x - (image_x - 512.0)).abs() < 1e-2, "{} vs {}", tile_x, image_x);
+                assert!((tile_y - image_y).abs() < 1e-2, "{} vs {}", tile_y, image_y);
+            }
+        }
+    }
+
+    // The default scene drawn the way the app draws it, straight into one framebuffer and in tiles
+    fn full_and_tiled(width: usize, height: usize, tiling: Tiling, bloom: crate::bloom::Bloom) -> (Vec<u32>, image::RgbImage) {
+        let scene = crate::scene::Scene::load(crate::scene::DEFAULT_SCENE_FILE).unwrap();
+        let background = crate::background::Background::starfield(7);
+        let options = crate::RenderOptions { bloom, ..crate::RenderOptions::new() };
+        let context = crate::SceneContext { planets: &scene.planets, sun: &scene.sun, background: &background, options };
+        let camera = Camera::new(nalgebra_glm::Vec3::new(3.0, 4.0, 14.0), nalgebra_glm::Vec3::new(0.0, 0.0, 0.0), nalgebra_glm::Vec3::new(0.0, 1.0, 0.0));
+        let mut draw = |framebuffer: &mut Framebuffer, matrices: &ViewMatrices| {
+            crate::render_scene(framebuffer, &context, matrices, 10.0);
+            framebuffer.resolve(&options.tone_mapping, &options.bloom);
+        };
+
+        let mut full = Framebuffer::new(width, height);
+        full.set_background_color(background.clear_color());
+        full.clear();
+        let view_matrix = crate::create_view_matrix(camera.eye, camera.center, camera.up);
+        let projection_matrix = camera.projection.matrix(width as f32, height as f32);
+        draw(&mut full, &ViewMatrices::new(view_matrix, projection_matrix, crate::create_viewport_matrix(width as f32, height as f32)));
+
+        let tiled = render(&camera, width, height, tiling, background.clear_color(), draw);
+        (full.buffer, tiled)
+    }
+
+    fn differing_pixels(full: &[u32], tiled: &image::RgbImage) -> usize {
+        let mut count = 0;
+        for (index, pixel) in tiled.pixels().enumerate() {
+            let color = full[index];
+            let expected = [(color >> 16) as u8, (color >> 8) as u8, color as u8];
+            if pixel.0 != expected { count += 1; }
+        }
+        count
+    }
+
+    #[test]
+    fn stitched_tiles_match_a_single_render() {
+        // Tiles that don't divide the image evenly, so the last row and column are partial
+        let (width, height) = (48, 40);
+        let bloom = crate::bloom::Bloom::new();
+        let (full, tiled) = full_and_tiled(width, height, Tiling { tile_size: 32, margin: bloom.reach() }, bloom);
+        assert_eq!(differing_pixels(&full, &tiled), 0);
+    }
+
+    #[test]
+    fn tiles_grow_with_the_margin() {
+        assert_eq!(Tiling::for_margin(0).tile_size, TILE_SIZE);
+        assert_eq!(Tiling::for_margin(512).tile_size, 2048);
+    }
+
+    #[test]
+    fn parses_sizes() {
+        assert_eq!(parse_size("7680x4320"), Some((7680, 4320)));
+        assert_eq!(parse_size("0x10"), None);
+        assert_eq!(parse_size("100"), None);
+    }
+}
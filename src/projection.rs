@@ -0,0 +1,93 @@
+use nalgebra_glm::Mat4;
+use std::f32::consts::PI;
+
+const MIN_FOV: f32 = 5.0 * PI / 180.0;
+const MAX_FOV: f32 = 120.0 * PI / 180.0;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ProjectionKind {
+    Perspective,
+    Orthographic,
+}
+
+pub struct Projection {
+    pub kind: ProjectionKind,
+    pub fov: f32, // Vertical field of view in radians, used in perspective
+    pub ortho_height: f32, // World units visible vertically, used in orthographic
+}
+
+impl Projection {
+    pub fn new() -> Self {
+        Projection {
+            kind: ProjectionKind::Perspective,
+            fov: 45.0 * PI / 180.0,
+            ortho_height: 18.0,
+        }
+    }
+
+    pub fn matrix(&self, width: f32, height: f32) -> Mat4 {
+        match self.kind {
+            ProjectionKind::Perspective => super::create_perspective_matrix(width, height, self.fov),
+            ProjectionKind::Orthographic => super::create_orthographic_matrix(width, height, self.ortho_height),
+        }
+    }
+
+    pub fn is_orthographic(&self) -> bool {
+        self.kind == ProjectionKind::Orthographic
+    }
+
+    pub fn set_fov(&mut self, fov: f32) {
+        self.fov = fov.clamp(MIN_FOV, MAX_FOV);
+    }
+
+    // Size of the view at the given distance; matching it keeps a subject's framing
+    pub fn view_height_at(&self, distance: f32) -> f32 {
+        2.0 * distance * (self.fov / 2.0).tan()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::Camera;
+    use nalgebra_glm::{Vec3, Vec4};
+
+    // Height on screen, in NDC, of a point one unit above the camera's center
+    fn framed_height(camera: &Camera) -> f32 {
+        let view = crate::create_view_matrix(camera.eye, camera.center, camera.up);
+        let clip = camera.projection.matrix(800.0, 600.0) * view * Vec4::new(camera.center.x, camera.center.y + 1.0, camera.center.z, 1.0);
+        clip.y / clip.w
+    }
+
+    #[test]
+    fn switching_projection_keeps_the_framing_at_the_center() {
+        let mut camera = Camera::new(Vec3::new(0.0, 0.0, 12.0), Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let perspective = framed_height(&camera);
+
+        camera.toggle_orthographic();
+        assert!(camera.projection.is_orthographic());
+        assert!((framed_height(&camera) - perspective).abs() < 1e-4);
+
+        camera.toggle_orthographic();
+        assert!(!camera.projection.is_orthographic());
+        assert!((framed_height(&camera) - perspective).abs() < 1e-4);
+    }
+
+    #[test]
+    fn view_height_grows_with_distance() {
+        let projection = Projection { fov: PI / 2.0, ..Projection::new() };
+        assert!((projection.view_height_at(1.0) - 2.0).abs() < 1e-5);
+        assert!((projection.view_height_at(5.0) - 10.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn fov_is_clamped() {
+        let mut projection = Projection::new();
+        projection.set_fov(0.0);
+        assert_eq!(projection.fov, MIN_FOV);
+        projection.set_fov(PI);
+        assert_eq!(projection.fov, MAX_FOV);
+        projection.set_fov(1.0);
+        assert_eq!(projection.fov, 1.0);
+    }
+}
@@ -0,0 +1,183 @@
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, RgbaImage};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use crate::framebuffer::Framebuffer;
+
+const GIF_SPEED: i32 = 10; // 1 is best quality, 30 is fastest
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum RecordFormat {
+    PngSequence,
+    Y4m,
+    Gif,
+}
+
+impl RecordFormat {
+    // Picked from the extension; anything else is a directory of PNGs
+    pub fn from_path(path: &str) -> RecordFormat {
+        if path.ends_with(".y4m") {
+            RecordFormat::Y4m
+        } else if path.ends_with(".gif") {
+            RecordFormat::Gif
+        } else {
+            RecordFormat::PngSequence
+        }
+    }
+
+    pub fn next(&self) -> RecordFormat {
+        match self {
+            RecordFormat::PngSequence => RecordFormat::Y4m,
+            RecordFormat::Y4m => RecordFormat::Gif,
+            RecordFormat::Gif => RecordFormat::PngSequence,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RecordFormat::PngSequence => "PNG sequence",
+            RecordFormat::Y4m => "Y4M",
+            RecordFormat::Gif => "GIF",
+        }
+    }
+
+    // Output path for a recording started now, with the right extension
+    pub fn path_for(&self, stem: &str) -> String {
+        match self {
+            RecordFormat::PngSequence => stem.to_string(),
+            RecordFormat::Y4m => format!("{}.y4m", stem),
+            RecordFormat::Gif => format!("{}.gif", stem),
+        }
+    }
+}
+
+enum Output {
+    PngSequence(String),
+    Y4m(BufWriter<File>),
+    Gif(GifEncoder<BufWriter<File>>),
+}
+
+/// Writes frames of a fixed size, one per call, at a fixed frame rate.
+pub struct Recorder {
+    pub path: String,
+    pub fps: u32,
+    pub frames: u32,
+    output: Output,
+    width: usize,
+    height: usize,
+}
+
+fn to_io_error(error: image::ImageError) -> io::Error {
+    io::Error::other(error)
+}
+
+// BT.601 studio range, the default ffmpeg assumes for Y4M
+fn rgb_to_yuv(color: u32) -> (u8, u8, u8) {
+    let r = ((color >> 16) & 0xFF) as f32;
+    let g = ((color >> 8) & 0xFF) as f32;
+    let b = (color & 0xFF) as f32;
+    let y = 16.0 + 0.257 * r + 0.504 * g + 0.098 * b;
+    let u = 128.0 - 0.148 * r - 0.291 * g + 0.439 * b;
+    let v = 128.0 + 0.439 * r - 0.368 * g - 0.071 * b;
+    (y.round() as u8, u.round() as u8, v.round() as u8)
+}
+
+impl Recorder {
+    pub fn start(path: &str, format: RecordFormat, width: usize, height: usize, fps: u32) -> io::Result<Self> {
+        let output = match format {
+            RecordFormat::PngSequence => {
+                fs::create_dir_all(path)?;
+                Output::PngSequence(path.to_string())
+            }
+            RecordFormat::Y4m => {
+                let mut writer = BufWriter::new(File::create(path)?);
+                // Full-resolution chroma (4:4:4), square pixels, progressive
+                writeln!(writer, "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444", width, height, fps)?;
+                Output::Y4m(writer)
+            }
+            RecordFormat::Gif => {
+                let mut encoder = GifEncoder::new_with_speed(BufWriter::new(File::create(path)?), GIF_SPEED);
+                encoder.set_repeat(Repeat::Infinite).map_err(to_io_error)?;
+                Output::Gif(encoder)
+            }
+        };
+
+        Ok(Recorder { path: path.to_string(), fps, frames: 0, output, width, height })
+    }
+
+    pub fn record_frame(&mut self, framebuffer: &Framebuffer) -> io::Result<()> {
+        if framebuffer.width != self.width || framebuffer.height != self.height {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "frame size changed during recording"));
+        }
+
+        match &mut self.output {
+            Output::PngSequence(dir) => {
+                let filename = format!("{}/frame_{:05}.png", dir, self.frames);
+                framebuffer.save_png(&filename).map_err(to_io_error)?;
+            }
+            Output::Y4m(writer) => {
+                let yuv: Vec<(u8, u8, u8)> = framebuffer.buffer.iter().map(|&color| rgb_to_yuv(color)).collect();
+                writer.write_all(b"FRAME\n")?;
+                writer.write_all(&yuv.iter().map(|p| p.0).collect::<Vec<u8>>())?;
+                writer.write_all(&yuv.iter().map(|p| p.1).collect::<Vec<u8>>())?;
+                writer.write_all(&yuv.iter().map(|p| p.2).collect::<Vec<u8>>())?;
+            }
+            Output::Gif(encoder) => {
+                let mut image = RgbaImage::new(self.width as u32, self.height as u32);
+                for (pixel, &color) in image.pixels_mut().zip(framebuffer.buffer.iter()) {
+                    *pixel = image::Rgba([(color >> 16) as u8, (color >> 8) as u8, color as u8, 255]);
+                }
+                let delay = Delay::from_numer_denom_ms(1000, self.fps);
+                encoder.encode_frame(Frame::from_parts(image, 0, 0, delay)).map_err(to_io_error)?;
+            }
+        }
+
+        self.frames += 1;
+        Ok(())
+    }
+
+    // Flushes what is buffered; the GIF trailer is written when the encoder is dropped
+    pub fn finish(self) -> io::Result<()> {
+        match self.output {
+            Output::PngSequence(_) => {}
+            Output::Y4m(mut writer) => writer.flush()?,
+            Output::Gif(encoder) => drop(encoder),
+        }
+        println!("Wrote {} frames to {}", self.frames, self.path);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn black_and_white_span_studio_range() {
+        assert_eq!(rgb_to_yuv(0x000000), (16, 128, 128));
+        assert_eq!(rgb_to_yuv(0xFFFFFF), (235, 128, 128));
+    }
+
+    #[test]
+    fn grays_have_no_chroma() {
+        for level in [0x20, 0x80, 0xC0] {
+            let (_, u, v) = rgb_to_yuv(level << 16 | level << 8 | level);
+            assert_eq!((u, v), (128, 128));
+        }
+    }
+
+    #[test]
+    fn primaries_match_bt601() {
+        assert_eq!(rgb_to_yuv(0xFF0000), (82, 90, 240));
+        assert_eq!(rgb_to_yuv(0x00FF00), (145, 54, 34));
+        assert_eq!(rgb_to_yuv(0x0000FF), (41, 240, 110));
+    }
+
+    #[test]
+    fn format_follows_the_extension() {
+        for format in [RecordFormat::PngSequence, RecordFormat::Y4m, RecordFormat::Gif] {
+            let path = format.path_for("recording");
+            assert_eq!(RecordFormat::from_path(&path).name(), format.name());
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use crate::celestial_body::{BodyInfo, CelestialBody};
+use crate::obj::Obj;
+use crate::vertex::Vertex;
+use crate::file_format::invalid_data;
+
+pub const DEFAULT_SCENE_FILE: &str = "./assets/scene.txt";
+
+/// The bodies to simulate, as read from a scene file.
+pub struct Scene {
+    pub planets: Vec<CelestialBody>,
+    pub sun: CelestialBody,
+}
+
+fn new_body(name: &str) -> CelestialBody {
+    CelestialBody {
+        name: name.to_string(),
+        vertex_array: Vec::new(),
+        shader_type: 0,
+        orbit_radius: 0.0,
+        rotation_speed: 0.0,
+        orbit_speed: 0.0,
+        scale: 1.0,
+        orbit_color: 0xFFFFFF,
+        emission: 0.0,
+        info: BodyInfo::default(),
+    }
+}
+
+// Applies one `key value` line to the body being read
+fn set_field(body: &mut CelestialBody, key: &str, value: &str, models: &mut HashMap<String, Vec<Vertex>>) -> Result<(), String> {
+    fn number<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+        value.parse::<T>().map_err(|_| format!("invalid number '{}'", value))
+    }
+
+    match key {
+        "model" => {
+            // Bodies usually share a mesh, so each file is only loaded once
+            if !models.contains_key(value) {
+                let obj = Obj::load(value).map_err(|e| format!("could not load model {}: {}", value, e))?;
+                models.insert(value.to_string(), obj.get_vertex_array());
+            }
+            body.vertex_array = models[value].clone();
+        }
+        "shader" => body.shader_type = number(value)?,
+        "orbit_radius" => body.orbit_radius = number(value)?,
+        "rotation_speed" => body.rotation_speed = number(value)?,
+        "orbit_speed" => body.orbit_speed = number(value)?,
+        "scale" => body.scale = number(value)?,
+        "orbit_color" => {
+            body.orbit_color = u32::from_str_radix(value, 16).map_err(|_| format!("invalid color '{}'", value))?
+        }
+        "emission" => body.emission = number(value)?,
+        "radius_km" => body.info.radius_km = number(value)?,
+        "mass_kg" => body.info.mass_kg = number(value)?,
+        "orbital_period_days" => body.info.orbital_period_days = number(value)?,
+        "day_length_hours" => body.info.day_length_hours = number(value)?,
+        "mean_temperature_c" => body.info.mean_temperature_c = number(value)?,
+        "moons" => body.info.moons = number(value)?,
+        "description" => body.info.description = value.to_string(),
+        _ => return Err(format!("unknown key '{}'", key)),
+    }
+    Ok(())
+}
+
+impl Scene {
+    pub fn load(filename: &str) -> io::Result<Self> {
+        let contents = fs::read_to_string(filename)?;
+        let mut models = HashMap::new();
+        let mut planets = Vec::new();
+        let mut sun = None;
+        // The body being read, and whether it is the sun
+        let mut current: Option<(CelestialBody, bool)> = None;
+
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: String| invalid_data(format!("{} line {}: {}", filename, number + 1, message));
+
+            let (key, value) = line.split_once(' ').map(|(key, value)| (key, value.trim())).unwrap_or((line, ""));
+            match key {
+                "planet" | "sun" => {
+                    if value.is_empty() {
+                        return Err(error(format!("missing name after '{}'", key)));
+                    }
+                    if key == "sun" && (sun.is_some() || matches!(current, Some((_, true)))) {
+                        return Err(error(format!("second sun '{}', a scene has only one", value)));
+                    }
+                    match current.take() {
+                        Some((body, true)) => sun = Some(body),
+                        Some((body, false)) => planets.push(body),
+                        None => {}
+                    }
+                    current = Some((new_body(value), key == "sun"));
+                }
+                _ => {
+                    let Some((body, _)) = current.as_mut() else {
+                        return Err(error(format!("'{}' before any planet or sun", key)));
+                    };
+                    set_field(body, key, value, &mut models).map_err(error)?;
+                }
+            }
+        }
+        match current {
+            Some((body, true)) => sun = Some(body),
+            Some((body, false)) => planets.push(body),
+            None => {}
+        }
+
+        let sun = sun.ok_or_else(|| invalid_data(format!("{}: no sun", filename)))?;
+        if let Some(body) = planets.iter().chain(std::iter::once(&sun)).find(|body| body.vertex_array.is_empty()) {
+            return Err(invalid_data(format!("{}: {} has no model", filename, body.name)));
+        }
+
+        Ok(Scene { planets, sun })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_a_second_sun() {
+        let path = std::env::temp_dir().join(format!("scene_two_suns_{}.txt", std::process::id()));
+        fs::write(&path, "sun Sun\nscale 2.5\nsun Other\n").unwrap();
+        let result = Scene::load(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+
+        let error = result.err().expect("a second sun should be an error");
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("line 3"), "{}", error);
+    }
+}
@@ -171,8 +171,8 @@ pub fn fragment_shader_earth(fragment: &Fragment, uniforms: &Uniforms) -> Color
     // Add moving clouds (white) overlay based on time
     let cloud_color = Color::new(255.0, 255.0, 255.0); // White clouds
     let cloud_speed = 0.1; // Adjust speed as desired
-    let cloud_pattern = ((fragment.vertex_position.x * 10.0 + uniforms.time as f32 * cloud_speed).sin() *
-                         (fragment.vertex_position.y * 10.0 + uniforms.time as f32 * cloud_speed).cos()).abs();
+    let cloud_pattern = ((fragment.vertex_position.x * 10.0 + uniforms.time * cloud_speed).sin() *
+                         (fragment.vertex_position.y * 10.0 + uniforms.time * cloud_speed).cos()).abs();
 
     // Blend clouds on top of the base color
     let final_color = if cloud_pattern > 0.7 {
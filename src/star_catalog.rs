@@ -0,0 +1,297 @@
+use nalgebra_glm::{Mat4, Vec3};
+use crate::background::{self, Star};
+use crate::framebuffer::Framebuffer;
+use crate::text;
+
+// Tilt of Earth's equator against the ecliptic (J2000)
+const OBLIQUITY_DEGREES: f32 = 23.44;
+
+const LINE_COLOR: u32 = 0x2A3A5C;
+const LABEL_COLOR: u32 = 0x6A7FB0;
+
+struct CatalogStar {
+    name: &'static str,
+    right_ascension: f32, // Hours
+    declination: f32, // Degrees
+    magnitude: f32,
+    color_index: f32, // B-V
+}
+
+const fn star(name: &'static str, right_ascension: f32, declination: f32, magnitude: f32, color_index: f32) -> CatalogStar {
+    CatalogStar { name, right_ascension, declination, magnitude, color_index }
+}
+
+// J2000 positions of the brightest stars, plus the ones needed to draw the constellations below
+const CATALOG: &[CatalogStar] = &[
+    star("Sirius", 6.7525, -16.716, -1.46, 0.00),
+    star("Canopus", 6.3992, -52.696, -0.74, 0.15),
+    star("Rigil Kentaurus", 14.6600, -60.834, -0.27, 0.71),
+    star("Arcturus", 14.2610, 19.182, -0.05, 1.23),
+    star("Vega", 18.6156, 38.784, 0.03, 0.00),
+    star("Capella", 5.2782, 45.998, 0.08, 0.80),
+    star("Rigel", 5.2423, -8.202, 0.13, -0.03),
+    star("Procyon", 7.6550, 5.225, 0.34, 0.42),
+    star("Achernar", 1.6286, -57.237, 0.46, -0.16),
+    star("Betelgeuse", 5.9195, 7.407, 0.50, 1.85),
+    star("Hadar", 14.0637, -60.373, 0.61, -0.23),
+    star("Altair", 19.8464, 8.868, 0.76, 0.22),
+    star("Acrux", 12.4433, -63.099, 0.76, -0.24),
+    star("Aldebaran", 4.5987, 16.509, 0.86, 1.54),
+    star("Antares", 16.4901, -26.432, 0.96, 1.83),
+    star("Spica", 13.4199, -11.161, 0.97, -0.23),
+    star("Pollux", 7.7553, 28.026, 1.14, 1.00),
+    star("Fomalhaut", 22.9608, -29.622, 1.16, 0.09),
+    star("Deneb", 20.6905, 45.280, 1.25, 0.09),
+    star("Mimosa", 12.7954, -59.689, 1.25, -0.24),
+    star("Regulus", 10.1395, 11.967, 1.35, -0.11),
+    star("Adhara", 6.9771, -28.972, 1.50, -0.21),
+    star("Castor", 7.5767, 31.888, 1.58, 0.03),
+    star("Shaula", 17.5601, -37.104, 1.62, -0.22),
+    star("Gacrux", 12.5194, -57.113, 1.63, 1.59),
+    star("Bellatrix", 5.4188, 6.350, 1.64, -0.22),
+    star("Elnath", 5.4382, 28.608, 1.65, -0.13),
+    star("Miaplacidus", 9.2200, -69.717, 1.67, 0.07),
+    star("Alnilam", 5.6036, -1.202, 1.69, -0.18),
+    star("Alnitak", 5.6793, -1.943, 1.74, -0.21),
+    star("Alioth", 12.9004, 55.960, 1.77, -0.02),
+    star("Dubhe", 11.0621, 61.751, 1.79, 1.07),
+    star("Mirfak", 3.4054, 49.861, 1.79, 0.48),
+    star("Wezen", 7.1399, -26.393, 1.83, 0.68),
+    star("Kaus Australis", 18.4029, -34.385, 1.85, -0.03),
+    star("Alkaid", 13.7923, 49.313, 1.86, -0.19),
+    star("Sargas", 17.6220, -42.998, 1.86, 0.40),
+    star("Menkalinan", 5.9921, 44.948, 1.90, 0.08),
+    star("Polaris", 2.5303, 89.264, 1.98, 0.60),
+    star("Mirzam", 6.3783, -17.956, 1.98, -0.23),
+    star("Alphard", 9.4598, -8.659, 1.99, 1.44),
+    star("Hamal", 2.1196, 23.462, 2.00, 1.15),
+    star("Algieba", 10.3329, 19.842, 2.08, 1.13),
+    star("Nunki", 18.9211, -26.297, 2.05, -0.13),
+    star("Saiph", 5.7959, -9.670, 2.09, -0.17),
+    star("Denebola", 11.8177, 14.572, 2.13, 0.09),
+    star("Mintaka", 5.5334, -0.299, 2.23, -0.22),
+    star("Mizar", 13.3988, 54.925, 2.23, 0.02),
+    star("Sadr", 20.3705, 40.257, 2.23, 0.67),
+    star("Schedar", 0.6751, 56.537, 2.24, 1.17),
+    star("Caph", 0.1529, 59.150, 2.28, 0.34),
+    star("Dschubba", 16.0056, -22.622, 2.29, -0.12),
+    star("Larawag", 16.8361, -34.293, 2.29, 1.15),
+    star("Merak", 11.0307, 56.382, 2.37, -0.02),
+    star("Girtab", 17.7081, -39.030, 2.39, -0.22),
+    star("Phecda", 11.8972, 53.695, 2.44, 0.04),
+    star("Navi", 0.9451, 60.717, 2.47, -0.15),
+    star("Aljanah", 20.7702, 33.970, 2.48, 1.03),
+    star("Zosma", 11.2351, 20.524, 2.56, 0.12),
+    star("Acrab", 16.0906, -19.806, 2.62, -0.07),
+    star("Ruchbah", 1.4302, 60.235, 2.68, 0.13),
+    star("Imai", 12.2524, -58.749, 2.79, -0.23),
+    star("Paikauhale", 16.5980, -28.216, 2.82, -0.25),
+    star("Fawaris", 19.7496, 45.131, 2.87, -0.03),
+    star("Fang", 15.9809, -26.114, 2.89, -0.19),
+    star("Alniyat", 16.3531, -25.593, 2.90, 0.13),
+    star("Ras Elased", 9.7642, 23.774, 2.98, 0.81),
+    star("Xamidimura", 16.8645, -38.047, 3.00, -0.20),
+    star("Iota Scorpii", 17.7931, -40.127, 2.99, 0.51),
+    star("Albireo", 19.5120, 27.960, 3.08, 1.13),
+    star("Megrez", 12.2571, 57.033, 3.31, 0.08),
+    star("Eta Scorpii", 17.2026, -43.239, 3.32, 0.41),
+    star("Chertan", 11.2373, 15.430, 3.33, -0.01),
+    star("Segin", 1.9066, 63.670, 3.37, -0.15),
+    star("Meissa", 5.5855, 9.934, 3.39, -0.16),
+    star("Adhafera", 10.2782, 23.417, 3.43, 0.31),
+    star("Eta Leonis", 10.1222, 16.763, 3.52, -0.03),
+    star("Zeta Scorpii", 16.9097, -42.362, 3.62, 1.37),
+    star("Rasalas", 9.8794, 26.007, 3.88, 1.22),
+];
+
+struct Constellation {
+    name: &'static str,
+    lines: &'static [(&'static str, &'static str)],
+}
+
+const CONSTELLATIONS: &[Constellation] = &[
+    Constellation {
+        name: "Orion",
+        lines: &[
+            ("Betelgeuse", "Meissa"), ("Meissa", "Bellatrix"), ("Betelgeuse", "Alnitak"),
+            ("Bellatrix", "Mintaka"), ("Mintaka", "Alnilam"), ("Alnilam", "Alnitak"),
+            ("Alnitak", "Saiph"), ("Mintaka", "Rigel"), ("Rigel", "Saiph"),
+        ],
+    },
+    Constellation {
+        name: "Ursa Major",
+        lines: &[
+            ("Dubhe", "Merak"), ("Merak", "Phecda"), ("Phecda", "Megrez"), ("Megrez", "Dubhe"),
+            ("Megrez", "Alioth"), ("Alioth", "Mizar"), ("Mizar", "Alkaid"),
+        ],
+    },
+    Constellation {
+        name: "Cassiopeia",
+        lines: &[("Caph", "Schedar"), ("Schedar", "Navi"), ("Navi", "Ruchbah"), ("Ruchbah", "Segin")],
+    },
+    Constellation {
+        name: "Crux",
+        lines: &[("Acrux", "Gacrux"), ("Mimosa", "Imai")],
+    },
+    Constellation {
+        name: "Cygnus",
+        lines: &[("Deneb", "Sadr"), ("Sadr", "Albireo"), ("Fawaris", "Sadr"), ("Sadr", "Aljanah")],
+    },
+    Constellation {
+        name: "Leo",
+        lines: &[
+            ("Regulus", "Eta Leonis"), ("Eta Leonis", "Algieba"), ("Algieba", "Adhafera"),
+            ("Adhafera", "Rasalas"), ("Rasalas", "Ras Elased"), ("Algieba", "Zosma"),
+            ("Zosma", "Denebola"), ("Denebola", "Chertan"), ("Chertan", "Regulus"), ("Zosma", "Chertan"),
+        ],
+    },
+    Constellation {
+        name: "Scorpius",
+        lines: &[
+            ("Acrab", "Dschubba"), ("Dschubba", "Fang"), ("Dschubba", "Alniyat"), ("Alniyat", "Antares"),
+            ("Antares", "Paikauhale"), ("Paikauhale", "Larawag"), ("Larawag", "Xamidimura"),
+            ("Xamidimura", "Zeta Scorpii"), ("Zeta Scorpii", "Eta Scorpii"), ("Eta Scorpii", "Sargas"),
+            ("Sargas", "Iota Scorpii"), ("Iota Scorpii", "Girtab"), ("Girtab", "Shaula"),
+        ],
+    },
+    Constellation {
+        name: "Gemini",
+        lines: &[("Castor", "Pollux")],
+    },
+];
+
+/// What is drawn over the catalog stars.
+#[derive(Clone, Copy, PartialEq)]
+pub enum SkyOverlay {
+    Off,
+    Lines,
+    LinesAndLabels,
+}
+
+impl SkyOverlay {
+    pub fn next(&self) -> SkyOverlay {
+        match self {
+            SkyOverlay::Off => SkyOverlay::Lines,
+            SkyOverlay::Lines => SkyOverlay::LinesAndLabels,
+            SkyOverlay::LinesAndLabels => SkyOverlay::Off,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SkyOverlay::Off => "off",
+            SkyOverlay::Lines => "constellation lines",
+            SkyOverlay::LinesAndLabels => "constellation lines and labels",
+        }
+    }
+}
+
+/// Direction of an equatorial position in the scene. The planets orbit in the XZ plane,
+/// so that is the ecliptic, with longitude 0 along +X. They move from +X towards +Z,
+/// which is counter-clockwise seen from -Y, so -Y is the north ecliptic pole.
+fn equatorial_to_scene(right_ascension: f32, declination: f32) -> Vec3 {
+    let ra = (right_ascension * 15.0).to_radians();
+    let dec = declination.to_radians();
+    let obliquity = OBLIQUITY_DEGREES.to_radians();
+
+    // Equatorial unit vector, then rotated about the vernal equinox into ecliptic coordinates
+    let (x, y, z) = (dec.cos() * ra.cos(), dec.cos() * ra.sin(), dec.sin());
+    let ecliptic_y = y * obliquity.cos() + z * obliquity.sin();
+    let ecliptic_z = -y * obliquity.sin() + z * obliquity.cos();
+
+    Vec3::new(x, -ecliptic_z, ecliptic_y)
+}
+
+// Ballesteros' approximation of the effective temperature from the B-V color index
+fn color_index_to_temperature(color_index: f32) -> f32 {
+    4600.0 * (1.0 / (0.92 * color_index + 1.7) + 1.0 / (0.92 * color_index + 0.62))
+}
+
+fn find(name: &str) -> Option<&'static CatalogStar> {
+    CATALOG.iter().find(|star| star.name == name)
+}
+
+fn direction_of(star: &CatalogStar) -> Vec3 {
+    equatorial_to_scene(star.right_ascension, star.declination)
+}
+
+pub fn catalog_stars() -> Vec<Star> {
+    CATALOG
+        .iter()
+        .map(|star| Star {
+            direction: direction_of(star),
+            magnitude: star.magnitude,
+            color: background::temperature_to_color(color_index_to_temperature(star.color_index)),
+        })
+        .collect()
+}
+
+/// Draws constellation lines and names on the sky, behind every body.
+pub fn render_overlay(framebuffer: &mut Framebuffer, overlay: SkyOverlay, view_matrix: &Mat4, sky_projection_matrix: &Mat4, viewport_matrix: &Mat4) {
+    if overlay == SkyOverlay::Off {
+        return;
+    }
+
+    let view_projection = background::sky_view_projection(view_matrix, sky_projection_matrix);
+    let project = |direction: Vec3| background::project_direction(&view_projection, viewport_matrix, direction);
+
+    framebuffer.set_current_id(None);
+    for constellation in CONSTELLATIONS {
+        framebuffer.set_current_color(LINE_COLOR);
+        for (from, to) in constellation.lines {
+            let (Some(from), Some(to)) = (find(from), find(to)) else {
+                continue;
+            };
+            // Both stars are in front of the camera, so the great circle between them projects
+            // to a straight line
+            let (Some(start), Some(end)) = (project(direction_of(from)), project(direction_of(to))) else {
+                continue;
+            };
+            framebuffer.line_3d(Vec3::new(start.0, start.1, f32::MAX), Vec3::new(end.0, end.1, f32::MAX), 1.0, false);
+        }
+
+        if overlay == SkyOverlay::LinesAndLabels {
+            // Centered on the average direction of the stars in the figure
+            let mut sum = Vec3::new(0.0, 0.0, 0.0);
+            for (from, to) in constellation.lines {
+                for star in [find(from), find(to)].into_iter().flatten() {
+                    sum += direction_of(star);
+                }
+            }
+            if let Some((x, y)) = project(sum.normalize()) {
+                let (width, _) = text::text_size(constellation.name, 1);
+                text::draw_text(framebuffer, x as i32 - width as i32 / 2, y as i32, constellation.name, LABEL_COLOR, 1, Some(f32::MAX));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn degrees_between(a: Vec3, b: Vec3) -> f32 {
+        a.normalize().dot(&b.normalize()).clamp(-1.0, 1.0).acos().to_degrees()
+    }
+
+    #[test]
+    fn polaris_is_near_the_north_ecliptic_pole() {
+        let north_pole = Vec3::new(0.0, -1.0, 0.0);
+        // The celestial pole is tilted from the ecliptic one by the obliquity
+        assert!((degrees_between(equatorial_to_scene(0.0, 90.0), north_pole) - OBLIQUITY_DEGREES).abs() < 1e-3);
+
+        let polaris = find("Polaris").unwrap();
+        let direction = equatorial_to_scene(polaris.right_ascension, polaris.declination);
+        assert!((direction.magnitude() - 1.0).abs() < 1e-5);
+        // Less than a degree from the celestial pole
+        assert!((degrees_between(direction, north_pole) - OBLIQUITY_DEGREES).abs() < 1.0);
+    }
+
+    #[test]
+    fn equinoxes_and_solstice_lie_on_the_ecliptic() {
+        // The vernal equinox is ecliptic longitude 0, along +X, and the summer solstice is 90°, along +Z
+        assert!(degrees_between(equatorial_to_scene(0.0, 0.0), Vec3::new(1.0, 0.0, 0.0)) < 1e-2);
+        assert!(degrees_between(equatorial_to_scene(6.0, OBLIQUITY_DEGREES), Vec3::new(0.0, 0.0, 1.0)) < 1e-2);
+        assert!(degrees_between(equatorial_to_scene(12.0, 0.0), Vec3::new(-1.0, 0.0, 0.0)) < 1e-2);
+    }
+}
@@ -0,0 +1,191 @@
+use nalgebra_glm::{Mat4, Vec3};
+use crate::camera::Camera;
+use crate::framebuffer::Framebuffer;
+use crate::viewport::Viewport;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum StereoMode {
+    Off,
+    Anaglyph, // Red/cyan glasses
+    SideBySide, // Left eye on the left half
+    CrossEye, // Right eye on the left half, for free viewing
+}
+
+pub struct StereoSettings {
+    pub mode: StereoMode,
+    pub eye_separation: f32, // Interocular distance in world units
+    pub convergence: f32, // Distance of the zero-parallax plane
+}
+
+struct EyeView {
+    view_matrix: Mat4,
+    projection_matrix: Mat4,
+}
+
+impl StereoMode {
+    pub fn next(&self) -> StereoMode {
+        match self {
+            StereoMode::Off => StereoMode::Anaglyph,
+            StereoMode::Anaglyph => StereoMode::SideBySide,
+            StereoMode::SideBySide => StereoMode::CrossEye,
+            StereoMode::CrossEye => StereoMode::Off,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            StereoMode::Off => "off",
+            StereoMode::Anaglyph => "red/cyan anaglyph",
+            StereoMode::SideBySide => "side-by-side",
+            StereoMode::CrossEye => "cross-eye",
+        }
+    }
+}
+
+impl StereoSettings {
+    pub fn new() -> Self {
+        StereoSettings {
+            mode: StereoMode::Off,
+            eye_separation: 0.3,
+            convergence: 10.0,
+        }
+    }
+
+    pub fn adjust_separation(&mut self, delta: f32) {
+        self.eye_separation = (self.eye_separation + delta).max(0.0);
+    }
+
+    pub fn adjust_convergence(&mut self, delta: f32) {
+        self.convergence = (self.convergence + delta).max(0.5);
+    }
+}
+
+// Parallel view axes with an off-axis frustum, so only objects at the convergence
+// distance line up and there is no vertical parallax as with toed-in cameras
+fn eye_view(camera: &Camera, settings: &StereoSettings, side: f32, width: f32, height: f32) -> EyeView {
+    let forward = (camera.center - camera.eye).normalize();
+    let right = forward.cross(&camera.up).normalize();
+    let offset: Vec3 = right * (side * settings.eye_separation / 2.0);
+
+    let view_matrix = super::create_view_matrix(camera.eye + offset, camera.center + offset, camera.up);
+    let mut projection_matrix = camera.projection.matrix(width, height);
+
+    // Shift x in clip space in proportion to the depth in front of the eye, which undoes the
+    // eye offset at the convergence distance. In perspective, where clip w is that depth, this
+    // is the off-axis frustum; in orthographic it shears the view into an oblique one.
+    let shift = projection_matrix[(0, 0)] * side * settings.eye_separation / 2.0 / settings.convergence;
+    projection_matrix[(0, 2)] -= shift;
+
+    EyeView { view_matrix, projection_matrix }
+}
+
+/// Renders the camera once per eye into the viewport and combines both images
+/// according to the stereo mode. `draw` renders the scene with the given matrices.
+/// The anaglyph draws the left eye into `eye_buffer`, which is kept from frame to frame.
+pub fn render<F>(framebuffer: &mut Framebuffer, eye_buffer: &mut Option<Framebuffer>, camera: &Camera, viewport: &Viewport, settings: &StereoSettings, mut draw: F)
+where
+    F: FnMut(&mut Framebuffer, Mat4, Mat4, &Viewport),
+{
+    match settings.mode {
+        StereoMode::Off => {
+            let view_matrix = super::create_view_matrix(camera.eye, camera.center, camera.up);
+            let projection_matrix = camera.projection.matrix(viewport.width as f32, viewport.height as f32);
+            framebuffer.clear_viewport(viewport);
+            framebuffer.set_scissor(Some(*viewport));
+            draw(framebuffer, view_matrix, projection_matrix, viewport);
+        }
+        StereoMode::Anaglyph => {
+            let (width, height) = (viewport.width as f32, viewport.height as f32);
+            let left = eye_view(camera, settings, -1.0, width, height);
+            let right = eye_view(camera, settings, 1.0, width, height);
+
+            // Made again only when the framebuffer is resized or its antialiasing changes
+            let fits = |eye: &Framebuffer| (eye.width, eye.height, eye.antialiasing()) == (framebuffer.width, framebuffer.height, framebuffer.antialiasing());
+            if !eye_buffer.as_ref().is_some_and(fits) {
+                let mut eye = Framebuffer::new(framebuffer.width, framebuffer.height);
+                eye.set_antialiasing(framebuffer.antialiasing());
+                *eye_buffer = Some(eye);
+            }
+            let left_eye = eye_buffer.as_mut().unwrap();
+            left_eye.clear_viewport(viewport);
+            left_eye.set_scissor(Some(*viewport));
+            draw(left_eye, left.view_matrix, left.projection_matrix, viewport);
+
+            framebuffer.clear_viewport(viewport);
+            framebuffer.set_scissor(Some(*viewport));
+            draw(framebuffer, right.view_matrix, right.projection_matrix, viewport);
+
+            // Red from the left eye, green and blue from the right
+            framebuffer.combine(left_eye, viewport, |left, right| [left[0], right[1], right[2]]);
+        }
+        StereoMode::SideBySide | StereoMode::CrossEye => {
+            let half = viewport.width / 2;
+            let left_half = Viewport::new(viewport.x, viewport.y, half, viewport.height, viewport.clear_color);
+            let right_half = Viewport::new(viewport.x + half, viewport.y, viewport.width - half, viewport.height, viewport.clear_color);
+            let (left_target, right_target) = if settings.mode == StereoMode::CrossEye {
+                (right_half, left_half)
+            } else {
+                (left_half, right_half)
+            };
+
+            for (side, target) in [(-1.0, left_target), (1.0, right_target)] {
+                let eye = eye_view(camera, settings, side, target.width as f32, target.height as f32);
+                framebuffer.clear_viewport(&target);
+                framebuffer.set_scissor(Some(target));
+                draw(framebuffer, eye.view_matrix, eye.projection_matrix, &target);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra_glm::Vec4;
+
+    fn camera() -> Camera {
+        Camera::new(Vec3::new(0.0, 0.0, 20.0), Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0))
+    }
+
+    // Where each eye sees a world point, in NDC
+    fn seen_by_both(camera: &Camera, settings: &StereoSettings, point: Vec3) -> [(f32, f32); 2] {
+        [-1.0, 1.0].map(|side| {
+            let eye = eye_view(camera, settings, side, 800.0, 600.0);
+            let clip = eye.projection_matrix * eye.view_matrix * Vec4::new(point.x, point.y, point.z, 1.0);
+            (clip.x / clip.w, clip.y / clip.w)
+        })
+    }
+
+    #[test]
+    fn eyes_sit_apart_along_the_camera_right() {
+        let camera = camera();
+        let settings = StereoSettings::new();
+        for (side, x) in [(-1.0, -0.15), (1.0, 0.15)] {
+            let eye = eye_view(&camera, &settings, side, 800.0, 600.0);
+            let origin = eye.view_matrix * Vec4::new(x, 0.0, 20.0, 1.0);
+            assert!(origin.xyz().magnitude() < 1e-5, "side {}: {:?}", side, origin);
+        }
+    }
+
+    #[test]
+    fn only_the_convergence_plane_lines_up() {
+        let mut camera = camera();
+        let settings = StereoSettings::new();
+        for orthographic in [false, true] {
+            if orthographic {
+                camera.toggle_orthographic();
+            }
+            // The convergence plane is 10 units in front of the camera, at z = 10
+            let [left, right] = seen_by_both(&camera, &settings, Vec3::new(1.0, 2.0, 10.0));
+            assert!((left.0 - right.0).abs() < 1e-5, "orthographic {}: {:?} {:?}", orthographic, left, right);
+
+            // Nearer points cross, so they stand out of the screen, and farther ones sink into it
+            let [left, right] = seen_by_both(&camera, &settings, Vec3::new(1.0, 2.0, 15.0));
+            assert!(left.0 > right.0, "orthographic {}", orthographic);
+            let [left, right] = seen_by_both(&camera, &settings, Vec3::new(1.0, 2.0, -5.0));
+            assert!(left.0 < right.0, "orthographic {}", orthographic);
+            // No vertical parallax
+            assert!((left.1 - right.1).abs() < 1e-5);
+        }
+    }
+}
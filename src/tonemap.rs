@@ -0,0 +1,173 @@
+use std::sync::OnceLock;
+use crate::color::Color;
+
+const EXPOSURE_STEP: f32 = 0.5; // Stops per key press
+pub const MAX_EXPOSURE: f32 = 8.0;
+const ENCODE_STEPS: usize = 65535; // Entries of the sRGB table, past the first
+
+// powf for every channel of every pixel is slow, so the sRGB curve is read from a table
+static ENCODE_TABLE: OnceLock<Vec<u8>> = OnceLock::new();
+
+/// Curve that brings linear HDR light into the 0-1 range of the screen.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Operator {
+    Clamp, // Cuts off at 1, like before HDR; the default, so nothing changes unless asked
+    Reinhard,
+    Aces,
+}
+
+impl Operator {
+    pub fn parse(name: &str) -> Option<Operator> {
+        match name {
+            "clamp" => Some(Operator::Clamp),
+            "reinhard" => Some(Operator::Reinhard),
+            "aces" => Some(Operator::Aces),
+            _ => None,
+        }
+    }
+
+    pub fn next(&self) -> Operator {
+        match self {
+            Operator::Clamp => Operator::Reinhard,
+            Operator::Reinhard => Operator::Aces,
+            Operator::Aces => Operator::Clamp,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Operator::Clamp => "clamp",
+            Operator::Reinhard => "Reinhard",
+            Operator::Aces => "ACES filmic",
+        }
+    }
+
+    fn apply(&self, value: f32) -> f32 {
+        match self {
+            Operator::Clamp => value.min(1.0),
+            Operator::Reinhard => value / (1.0 + value),
+            // Krzysztof Narkowicz's fit of the ACES reference curve, which overshoots 1 a little
+            Operator::Aces => ((value * (2.51 * value + 0.03)) / (value * (2.43 * value + 0.59) + 0.14)).min(1.0),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct ToneMapping {
+    pub operator: Operator,
+    pub exposure: f32, // In stops; each one doubles the light
+}
+
+impl ToneMapping {
+    pub fn new() -> Self {
+        ToneMapping { operator: Operator::Clamp, exposure: 0.0 }
+    }
+
+    pub fn brighten(&mut self, brighter: bool) {
+        let step = if brighter { EXPOSURE_STEP } else { -EXPOSURE_STEP };
+        self.exposure = (self.exposure + step).clamp(-MAX_EXPOSURE, MAX_EXPOSURE);
+    }
+
+    /// Exposes, tone maps and sRGB-encodes a linear color into a screen pixel.
+    pub fn map(&self, linear: [f32; 3]) -> u32 {
+        let scale = self.exposure.exp2();
+        let table = ENCODE_TABLE.get_or_init(|| {
+            (0..=ENCODE_STEPS)
+                .map(|step| (linear_to_srgb(step as f32 / ENCODE_STEPS as f32) * 255.0).round() as u8)
+                .collect()
+        });
+        let channel = |value: f32| {
+            let mapped = self.operator.apply((value * scale).max(0.0)).clamp(0.0, 1.0);
+            table[(mapped * ENCODE_STEPS as f32).round() as usize] as u32
+        };
+        (channel(linear[0]) << 16) | (channel(linear[1]) << 8) | channel(linear[2])
+    }
+}
+
+pub fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+// 0xRRGGBB as sRGB to linear light
+pub fn hex_to_linear(color: u32) -> [f32; 3] {
+    let channel = |shift: u32| srgb_to_linear(((color >> shift) & 0xFF) as f32 / 255.0);
+    [channel(16), channel(8), channel(0)]
+}
+
+// Shader colors are sRGB on a 0-255 scale, but may go past 255 for bright light
+pub fn color_to_linear(color: &Color) -> [f32; 3] {
+    let channel = |value: f32| srgb_to_linear(value.max(0.0) / 255.0);
+    [channel(color.r), channel(color.g), channel(color.b)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CURVES: [Operator; 2] = [Operator::Reinhard, Operator::Aces];
+
+    #[test]
+    fn curves_start_at_black_and_only_rise() {
+        for operator in CURVES {
+            assert_eq!(operator.apply(0.0), 0.0, "{}", operator.name());
+            let mut previous = 0.0;
+            for step in 1..=2000 {
+                let mapped = operator.apply(step as f32 * 0.01);
+                assert!(mapped >= previous, "{} falls at {}", operator.name(), step as f32 * 0.01);
+                previous = mapped;
+            }
+        }
+    }
+
+    #[test]
+    fn curves_stay_on_screen() {
+        for operator in CURVES {
+            for value in [0.0, 0.001, 0.5, 1.0, 4.0, 100.0, 1e6] {
+                let mapped = operator.apply(value);
+                assert!((0.0..=1.0).contains(&mapped), "{} maps {} to {}", operator.name(), value, mapped);
+            }
+        }
+    }
+
+    #[test]
+    fn srgb_table_ends_and_middle() {
+        let tone_mapping = ToneMapping::new();
+        assert_eq!(tone_mapping.map([0.0; 3]), 0x000000);
+        assert_eq!(tone_mapping.map([1.0; 3]), 0xFFFFFF);
+        // Half the light is about 73% of the way up in sRGB
+        assert_eq!(tone_mapping.map([0.5; 3]), 0xBCBCBC);
+        assert_eq!(hex_to_linear(tone_mapping.map([0.2, 0.5, 0.8])).map(|channel| (channel * 100.0).round()), [20.0, 50.0, 80.0]);
+    }
+
+    #[test]
+    fn overbright_light_keeps_its_detail_when_tone_mapped() {
+        // The sun at twice and four times what the screen can show
+        let (bright, brighter) = ([2.0, 1.6, 1.2], [4.0, 3.2, 2.4]);
+
+        let clamped = ToneMapping::new();
+        assert_eq!(clamped.map(bright), clamped.map(brighter));
+
+        for operator in CURVES {
+            let tone_mapping = ToneMapping { operator, ..ToneMapping::new() };
+            let (low, high) = (tone_mapping.map(bright), tone_mapping.map(brighter));
+            assert_ne!(low, high, "{}", operator.name());
+            assert!(low & 0xFF < (low >> 16) & 0xFF, "{} should keep the hue", operator.name());
+        }
+
+        // Darkening brings it back in range, where it is no longer white
+        let darker = ToneMapping { exposure: -2.0, ..ToneMapping::new() };
+        assert_ne!(darker.map(brighter), 0xFFFFFF);
+    }
+}
@@ -3,55 +3,94 @@ use crate::fragment::Fragment;
 use crate::vertex::Vertex;
 use crate::color::Color;
 
-fn calculate_bounding_box(v1: &Vec3, v2: &Vec3, v3: &Vec3) -> (i32, i32, i32, i32) {
-	let min_x = v1.x.min(v2.x).min(v3.x).floor() as i32;
-	let min_y = v1.y.min(v2.y).min(v3.y).floor() as i32;
-	let max_x = v1.x.max(v2.x).max(v3.x).ceil() as i32;
-	let max_y = v1.y.max(v2.y).max(v3.y).ceil() as i32;
+// Cut to the framebuffer, since triangles clipped at the near plane can reach far past it
+fn calculate_bounding_box(v1: &Vec3, v2: &Vec3, v3: &Vec3, width: usize, height: usize) -> (i32, i32, i32, i32) {
+	let min_x = (v1.x.min(v2.x).min(v3.x).floor() as i32).max(0);
+	let min_y = (v1.y.min(v2.y).min(v3.y).floor() as i32).max(0);
+	let max_x = (v1.x.max(v2.x).max(v3.x).ceil() as i32).min(width as i32 - 1);
+	let max_y = (v1.y.max(v2.y).max(v3.y).ceil() as i32).min(height as i32 - 1);
 
 	(min_x, min_y, max_x, max_y)
 }
 
-pub fn triangle(v1: &Vertex, v2: &Vertex, v3: &Vertex) -> Vec<Fragment> {
+/// Rasterizes the triangle, testing the given sample offsets in each pixel. With
+/// `per_sample` every covered sample gets its own fragment (supersampling); otherwise
+/// each pixel gets one fragment with the coverage of all its samples (multisampling).
+/// Only pixels inside a `width` x `height` framebuffer are visited.
+pub fn triangle(v1: &Vertex, v2: &Vertex, v3: &Vertex, samples: &[Vec2], per_sample: bool, width: usize, height: usize) -> Vec<Fragment> {
 	let mut fragments = Vec::new();
 
 	let (a, b, c) = (v1.transformed_position, v2.transformed_position, v3.transformed_position);
 
-	let (min_x, min_y, max_x, max_y) = calculate_bounding_box(&a, &b, &c);
+	let (min_x, min_y, max_x, max_y) = calculate_bounding_box(&a, &b, &c, width, height);
 
 	let triangle_area =edge_function(&a, &b, &c);
 	let light_dir = Vec3::new(0.0, 0.0, 1.0);
-	
 
-	for y in min_y..=max_y {
-		for x in min_x..=max_x {
-			let point = Vec3::new(x as f32, y as f32 +0.5, 0.0);
+	// Depth is linear in screen space, so samples can work out their own from the slope
+	let depth_at = |point: &Vec3| {
+		let (w1, w2, w3) = barycentric(point, &a, &b, &c, triangle_area);
+		a.z * w1 + b.z * w2 + c.z * w3
+	};
+	let origin_depth = depth_at(&Vec3::zeros());
+	let depth_slope = Vec2::new(
+		depth_at(&Vec3::new(1.0, 0.0, 0.0)) - origin_depth,
+		depth_at(&Vec3::new(0.0, 1.0, 0.0)) - origin_depth,
+	);
+
+	let fragment_at = |point: &Vec3, coverage: u32| {
+		let (w1,w2,w3) = barycentric(point,&a,&b,&c,triangle_area);
+
+		let normal = v1.transformed_normal * w1 + v2.transformed_normal * w2 + v3.transformed_normal * w3;
+		let normal = normal.normalize();
 
-			let (w1,w2,w3) = barycentric(&point,&a,&b,&c,triangle_area);
-			
-			if w1 >= 0.0 && w1 <= 1.0 &&
-			   w2 >= 0.0 && w2 <= 1.0 &&
-			   w3 >= 0.0 && w3 <= 1.0 {
+		let intensity = dot(&normal, &light_dir).max(0.0);
 
-				   let normal = v1.transformed_normal * w1 + v2.transformed_normal * w2 + v3.transformed_normal * w3;
-				   let normal = normal.normalize();
+		let color = Color::new(100.0,100.0,100.0);
 
-				   let intensity = dot(&normal, &light_dir).max(0.0);
+		let depth = a.z * w1 + b.z * w2 + c.z * w3;
 
-				   let color = Color::new(100.0,100.0,100.0);
+		let vertex_position = v1.position * w1 + v2.position * w2 + v3.position * w3;
 
-				   let depth = a.z * w1 + b.z * w2 + c.z * w3;
+		let mut fragment = Fragment::new(
+			Vec2::new(point.x, point.y),
+			color,
+			depth,
+			normal,
+			intensity,
+			vertex_position,
+		);
+		fragment.coverage = coverage;
+		fragment.depth_slope = depth_slope;
+		fragment
+	};
 
-				   let vertex_position = v1.position * w1 + v2.position * w2 + v3.position * w3;
+	for y in min_y..=max_y {
+		for x in min_x..=max_x {
+			let mut coverage = 0u32;
+			let mut centroid = Vec2::zeros();
+
+			for (index, offset) in samples.iter().enumerate() {
+				let point = Vec3::new(x as f32 + offset.x, y as f32 + offset.y, 0.0);
+
+				let (w1,w2,w3) = barycentric(&point,&a,&b,&c,triangle_area);
+
+				if w1 >= 0.0 && w1 <= 1.0 &&
+				   w2 >= 0.0 && w2 <= 1.0 &&
+				   w3 >= 0.0 && w3 <= 1.0 {
+					if per_sample {
+						fragments.push(fragment_at(&point, 1 << index));
+					} else {
+						coverage |= 1 << index;
+						centroid += offset;
+					}
+				}
+			}
 
-				   fragments.push(Fragment::new(
-					   Vec2::new(x as f32, y as f32),
-					   color,
-					   depth,
-					   normal,
-					   intensity,
-					   vertex_position,
-				   ));
+			// Shaded once, at the center of the covered samples so it stays inside the triangle
+			if coverage != 0 {
+				let centroid = centroid / coverage.count_ones() as f32;
+				fragments.push(fragment_at(&Vec3::new(x as f32 + centroid.x, y as f32 + centroid.y, 0.0), coverage));
 			}
 		}
 	}
@@ -70,4 +109,35 @@ pub fn barycentric(p: &Vec3, a:&Vec3, b: &Vec3, c: &Vec3, area: f32) -> (f32, f3
 
 fn edge_function(a: &Vec3, b: &Vec3, c: &Vec3) -> f32 {
 	(c.x - a.x ) * (b.y - a.y) -(c.y-a.y ) *(b.x -a.x)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn vertex_at(x: f32, y: f32) -> Vertex {
+		let mut vertex = Vertex::new(Vec3::zeros(), Vec3::new(0.0, 0.0, 1.0), Vec2::zeros());
+		vertex.transformed_position = Vec3::new(x, y, 0.5);
+		vertex
+	}
+
+	#[test]
+	fn huge_triangle_only_covers_the_framebuffer() {
+		// What a triangle cut at the near plane looks like: corners thousands of pixels out
+		let (a, b, c) = (vertex_at(-4000.0, -3000.0), vertex_at(5000.0, -2000.0), vertex_at(300.0, 6000.0));
+		let (width, height) = (60, 40);
+
+		let fragments = triangle(&a, &b, &c, &[Vec2::new(0.5, 0.5)], false, width, height);
+		assert_eq!(fragments.len(), width * height);
+		assert!(fragments.iter().all(|fragment| {
+			fragment.position.x >= 0.0 && fragment.position.x < width as f32 &&
+			fragment.position.y >= 0.0 && fragment.position.y < height as f32
+		}));
+	}
+
+	#[test]
+	fn triangle_off_the_framebuffer_gives_nothing() {
+		let (a, b, c) = (vertex_at(-50.0, -50.0), vertex_at(-10.0, -50.0), vertex_at(-30.0, -10.0));
+		assert!(triangle(&a, &b, &c, &[Vec2::new(0.5, 0.5)], false, 60, 40).is_empty());
+	}
+}
@@ -0,0 +1,95 @@
+use nalgebra_glm::Mat4;
+
+/// A sub-rectangle of the framebuffer that one camera renders into.
+#[derive(Clone, Copy)]
+pub struct Viewport {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+    pub clear_color: u32,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Layout {
+    Single,
+    PictureInPicture, // Bird's-eye minimap inset over the main view
+    SplitScreen, // Main view next to a view of the compared body
+}
+
+impl Viewport {
+    pub fn new(x: usize, y: usize, width: usize, height: usize, clear_color: u32) -> Self {
+        Viewport { x, y, width, height, clear_color }
+    }
+
+    pub fn matrix(&self) -> Mat4 {
+        super::create_viewport_matrix_at(self.x as f32, self.y as f32, self.width as f32, self.height as f32)
+    }
+
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        x >= self.x as f32 && x < (self.x + self.width) as f32 &&
+        y >= self.y as f32 && y < (self.y + self.height) as f32
+    }
+}
+
+impl Layout {
+    pub fn next(&self) -> Layout {
+        match self {
+            Layout::Single => Layout::PictureInPicture,
+            Layout::PictureInPicture => Layout::SplitScreen,
+            Layout::SplitScreen => Layout::Single,
+        }
+    }
+
+    // The first viewport always belongs to the main camera
+    pub fn viewports(&self, width: usize, height: usize, clear_color: u32) -> Vec<Viewport> {
+        match self {
+            Layout::Single => vec![Viewport::new(0, 0, width, height, clear_color)],
+            Layout::PictureInPicture => {
+                let inset = width.min(height) / 3;
+                let margin = 10;
+                // Too small to fit the inset beside its margins, so show the main view alone
+                if inset == 0 || inset + 2 * margin > width.min(height) {
+                    return Layout::Single.viewports(width, height, clear_color);
+                }
+                vec![
+                    Viewport::new(0, 0, width, height, clear_color),
+                    Viewport::new(width - inset - margin, margin, inset, inset, 0x111122),
+                ]
+            }
+            Layout::SplitScreen => {
+                let half = width / 2;
+                vec![
+                    Viewport::new(0, 0, half, height, clear_color),
+                    Viewport::new(half, 0, width - half, height, 0x222244),
+                ]
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inset_sits_in_the_top_right_corner() {
+        let viewports = Layout::PictureInPicture.viewports(800, 600, 0);
+        assert_eq!(viewports.len(), 2);
+        let inset = viewports[1];
+        assert_eq!((inset.x, inset.y, inset.width, inset.height), (590, 10, 200, 200));
+
+        let inset = Layout::PictureInPicture.viewports(40, 600, 0)[1];
+        assert_eq!((inset.x, inset.width), (17, 13));
+    }
+
+    #[test]
+    fn tiny_framebuffer_drops_the_inset() {
+        // `--size 40x600 --scale 0.25` renders at 10x150
+        for (width, height) in [(10, 150), (25, 600), (0, 0)] {
+            let viewports = Layout::PictureInPicture.viewports(width, height, 0);
+            assert_eq!(viewports.len(), 1);
+            assert_eq!((viewports[0].width, viewports[0].height), (width, height));
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use nalgebra_glm::Vec3;
+
+const CLEARANCE: f32 = 1.0; // Extra distance kept from every bounding sphere
+const ARC_SAMPLES: usize = 32;
+const MAX_ITERATIONS: usize = 32;
+
+#[derive(Clone, Copy)]
+pub enum Easing {
+    Linear,
+    SmoothStep,
+    EaseInOutCubic,
+    EaseInOutSine,
+}
+
+pub struct Obstacle {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+pub struct WarpSettings {
+    pub easing: Easing,
+    pub min_duration: f32,
+    pub max_duration: f32,
+    pub seconds_per_unit: f32,
+}
+
+impl Easing {
+    pub fn apply(&self, t: f32) -> f32 {
+        match self {
+            Easing::Linear => t,
+            Easing::SmoothStep => t * t * (3.0 - 2.0 * t),
+            Easing::EaseInOutCubic => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+                }
+            }
+            Easing::EaseInOutSine => -((std::f32::consts::PI * t).cos() - 1.0) / 2.0,
+        }
+    }
+
+    pub fn next(&self) -> Easing {
+        match self {
+            Easing::Linear => Easing::SmoothStep,
+            Easing::SmoothStep => Easing::EaseInOutCubic,
+            Easing::EaseInOutCubic => Easing::EaseInOutSine,
+            Easing::EaseInOutSine => Easing::Linear,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Easing::Linear => "linear",
+            Easing::SmoothStep => "smoothstep",
+            Easing::EaseInOutCubic => "ease-in-out cubic",
+            Easing::EaseInOutSine => "ease-in-out sine",
+        }
+    }
+}
+
+impl WarpSettings {
+    pub fn new() -> Self {
+        WarpSettings {
+            easing: Easing::SmoothStep,
+            min_duration: 1.0,
+            max_duration: 4.0,
+            seconds_per_unit: 0.1,
+        }
+    }
+
+    pub fn duration_for(&self, distance: f32) -> f32 {
+        (self.min_duration + distance * self.seconds_per_unit).min(self.max_duration)
+    }
+}
+
+pub fn quadratic_bezier(start: Vec3, control: Vec3, end: Vec3, t: f32) -> Vec3 {
+    let u = 1.0 - t;
+    start * (u * u) + control * (2.0 * u * t) + end * (t * t)
+}
+
+// Length of the curve, approximated by the sampled polyline
+pub fn arc_length(start: Vec3, control: Vec3, end: Vec3) -> f32 {
+    let mut length = 0.0;
+    let mut previous = start;
+    for i in 1..=ARC_SAMPLES {
+        let point = quadratic_bezier(start, control, end, i as f32 / ARC_SAMPLES as f32);
+        length += (point - previous).magnitude();
+        previous = point;
+    }
+    length
+}
+
+/// Control point of a quadratic Bézier from `start` to `end` that keeps clear of every obstacle.
+/// Starts from a straight line and pushes the control point away from the deepest intrusion
+/// until no sample of the curve is inside an obstacle.
+pub fn plan_arc(start: Vec3, end: Vec3, obstacles: &[Obstacle]) -> Vec3 {
+    let mut control = (start + end) * 0.5;
+
+    // Preferred escape direction when the path runs straight through a center: over the ecliptic
+    let path = end - start;
+    let fallback = path
+        .cross(&Vec3::new(0.0, 1.0, 0.0))
+        .cross(&path)
+        .try_normalize(1.0e-6)
+        .unwrap_or(Vec3::new(0.0, 1.0, 0.0));
+
+    for _ in 0..MAX_ITERATIONS {
+        let mut deepest: Option<(Vec3, f32)> = None;
+
+        for obstacle in obstacles {
+            let radius = obstacle.radius + CLEARANCE;
+
+            // Nothing to avoid if the warp starts or ends inside the clearance zone
+            if (start - obstacle.center).magnitude() < radius || (end - obstacle.center).magnitude() < radius {
+                continue;
+            }
+
+            for i in 1..ARC_SAMPLES {
+                let point = quadratic_bezier(start, control, end, i as f32 / ARC_SAMPLES as f32);
+                let offset = point - obstacle.center;
+                let depth = radius - offset.magnitude();
+
+                if depth > 0.0 && deepest.is_none_or(|(_, d)| depth > d) {
+                    let direction = offset.try_normalize(1.0e-3).unwrap_or(fallback);
+                    deepest = Some((direction, depth));
+                }
+            }
+        }
+
+        match deepest {
+            // The curve midpoint moves half as far as the control point
+            Some((direction, depth)) => control += direction * (depth * 2.0 + 0.01),
+            None => break,
+        }
+    }
+
+    control
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EASINGS: [Easing; 4] = [Easing::Linear, Easing::SmoothStep, Easing::EaseInOutCubic, Easing::EaseInOutSine];
+
+    #[test]
+    fn easings_start_and_end_in_place() {
+        for easing in EASINGS {
+            assert!(easing.apply(0.0).abs() < 1e-6, "{}", easing.name());
+            assert!((easing.apply(1.0) - 1.0).abs() < 1e-6, "{}", easing.name());
+            assert!((easing.apply(0.5) - 0.5).abs() < 1e-6, "{}", easing.name());
+        }
+    }
+
+    #[test]
+    fn easings_never_go_backwards() {
+        for easing in EASINGS {
+            let mut previous = 0.0;
+            for i in 1..=100 {
+                let value = easing.apply(i as f32 / 100.0);
+                assert!(value >= previous, "{}", easing.name());
+                previous = value;
+            }
+        }
+    }
+
+    #[test]
+    fn warp_duration_is_clamped() {
+        let settings = WarpSettings::new();
+        assert_eq!(settings.duration_for(0.0), settings.min_duration);
+        assert_eq!(settings.duration_for(1.0e6), settings.max_duration);
+    }
+
+    #[test]
+    fn bezier_runs_from_start_to_end() {
+        let (start, control, end) = (Vec3::new(0.0, 0.0, 0.0), Vec3::new(5.0, 5.0, 0.0), Vec3::new(10.0, 0.0, 0.0));
+        assert_eq!(quadratic_bezier(start, control, end, 0.0), start);
+        assert_eq!(quadratic_bezier(start, control, end, 1.0), end);
+        // The midpoint is halfway between the chord and the control point
+        assert!((quadratic_bezier(start, control, end, 0.5) - Vec3::new(5.0, 2.5, 0.0)).magnitude() < 1e-5);
+    }
+
+    #[test]
+    fn straight_arc_length_is_the_distance() {
+        let (start, end) = (Vec3::new(1.0, 2.0, 3.0), Vec3::new(4.0, 6.0, 3.0));
+        assert!((arc_length(start, (start + end) * 0.5, end) - 5.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn arc_without_obstacles_is_a_straight_line() {
+        let (start, end) = (Vec3::new(-10.0, 0.0, 0.0), Vec3::new(10.0, 0.0, 0.0));
+        assert_eq!(plan_arc(start, end, &[]), Vec3::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn arc_clears_an_obstacle_in_the_way() {
+        let (start, end) = (Vec3::new(-10.0, 0.0, 0.0), Vec3::new(10.0, 0.0, 0.0));
+        let obstacle = Obstacle { center: Vec3::new(0.0, 0.0, 0.0), radius: 3.0 };
+        let control = plan_arc(start, end, std::slice::from_ref(&obstacle));
+
+        for i in 0..=ARC_SAMPLES {
+            let point = quadratic_bezier(start, control, end, i as f32 / ARC_SAMPLES as f32);
+            assert!((point - obstacle.center).magnitude() >= obstacle.radius + CLEARANCE - 1e-3);
+        }
+    }
+}